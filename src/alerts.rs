@@ -0,0 +1,321 @@
+//! Alerts for stats that stay above or below a threshold for a certain amount of time.
+
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::stats::AllStats;
+
+/// How a stat is compared to a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Comparator {
+    /// The stat must be greater than the threshold.
+    #[serde(rename = ">")]
+    GreaterThan,
+    /// The stat must be greater than or equal to the threshold.
+    #[serde(rename = ">=")]
+    GreaterThanOrEqual,
+    /// The stat must be less than the threshold.
+    #[serde(rename = "<")]
+    LessThan,
+    /// The stat must be less than or equal to the threshold.
+    #[serde(rename = "<=")]
+    LessThanOrEqual,
+}
+
+impl Comparator {
+    /// Determines whether the provided value compares to the provided threshold as required.
+    fn matches(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparator::GreaterThan => value > threshold,
+            Comparator::GreaterThanOrEqual => value >= threshold,
+            Comparator::LessThan => value < threshold,
+            Comparator::LessThanOrEqual => value <= threshold,
+        }
+    }
+
+    /// Gets the symbol for this comparator.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparator::GreaterThan => ">",
+            Comparator::GreaterThanOrEqual => ">=",
+            Comparator::LessThan => "<",
+            Comparator::LessThanOrEqual => "<=",
+        }
+    }
+}
+
+/// A rule describing when an alert should fire.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct AlertRule {
+    /// The name of the rule.
+    pub name: String,
+    /// The path to the stat to check, with each part separated by a `.`, such as `cpu.aggregateLoadPercent` or `filesystems.0.usedMb`.
+    pub metric: String,
+    /// How the stat is compared to the threshold.
+    pub comparator: Comparator,
+    /// The value to compare the stat to.
+    pub threshold: f64,
+    /// The number of seconds the stat must continuously match the rule before the alert fires.
+    #[serde(default)]
+    pub for_seconds: u64,
+}
+
+/// The state of an alert.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertState {
+    /// The stat doesn't match the rule, and the alert has never fired.
+    Inactive,
+    /// The stat matches the rule, but hasn't for long enough for the alert to fire.
+    Pending,
+    /// The stat has matched the rule for long enough for the alert to fire.
+    Firing,
+    /// The alert fired, but the stat doesn't match the rule anymore.
+    Resolved,
+}
+
+/// The status of an alert.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertStatus {
+    /// The rule for the alert.
+    pub rule: AlertRule,
+    /// The current state of the alert.
+    pub state: AlertState,
+    /// The time the alert entered its current state. `None` if the alert has never left the inactive state.
+    pub since: Option<DateTime<Local>>,
+    /// The most recent value of the stat. `None` if the stat wasn't found.
+    pub value: Option<f64>,
+    /// A description of the current status of the alert.
+    pub message: String,
+}
+
+impl AlertStatus {
+    fn new(rule: AlertRule) -> AlertStatus {
+        let message = build_message(&rule, None);
+        AlertStatus {
+            rule,
+            state: AlertState::Inactive,
+            since: None,
+            value: None,
+            message,
+        }
+    }
+}
+
+/// A notification that an alert started firing or was resolved.
+#[derive(Debug, Clone)]
+pub struct AlertNotification {
+    /// The status of the alert after it started firing or was resolved.
+    pub status: AlertStatus,
+    /// The stats that caused the alert to start firing or be resolved.
+    pub stats: AllStats,
+}
+
+/// Something to notify when alerts start firing or are resolved.
+pub trait AlertNotifier: Send {
+    /// Sends a notification. Must not block while the notification is delivered.
+    fn notify(&self, notification: AlertNotification);
+}
+
+/// Tracks the states of alerts as new stats are collected.
+pub struct Alerts {
+    /// The status of the alert for each rule.
+    statuses: Vec<AlertStatus>,
+    /// The things to notify when alerts start firing or are resolved.
+    notifiers: Vec<Box<dyn AlertNotifier>>,
+}
+
+impl Alerts {
+    /// Creates an `Alerts` with all the provided rules in the inactive state.
+    ///
+    /// # Arguments
+    /// * `rules` - The rules for the alerts.
+    /// * `notifiers` - The things to notify when alerts start firing or are resolved.
+    pub fn new(rules: Vec<AlertRule>, notifiers: Vec<Box<dyn AlertNotifier>>) -> Alerts {
+        Alerts {
+            statuses: rules.into_iter().map(AlertStatus::new).collect(),
+            notifiers,
+        }
+    }
+
+    /// Gets the statuses of all the alerts.
+    pub fn statuses(&self) -> &[AlertStatus] {
+        &self.statuses
+    }
+
+    /// Updates the states of the alerts based on newly collected stats, and notifies the notifiers of any alerts that started firing or were resolved. Alerts whose stat has no value in the new stats keep their current state.
+    ///
+    /// # Arguments
+    /// * `stats` - The newly collected stats.
+    ///
+    /// Returns the statuses of the alerts whose states changed.
+    pub fn evaluate(&mut self, stats: &AllStats) -> Vec<AlertStatus> {
+        if self.statuses.is_empty() {
+            return Vec::new();
+        }
+
+        let stats_value = match serde_json::to_value(stats) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Error evaluating alerts: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let now = stats.collection_time;
+        let mut changed_statuses = Vec::new();
+        for status in self.statuses.iter_mut() {
+            let value = get_stat_value(&stats_value, &status.rule.metric);
+            status.value = value;
+            status.message = build_message(&status.rule, value);
+            let matches = match value {
+                Some(x) => status.rule.comparator.matches(x, status.rule.threshold),
+                // the stat can be missing just because its collector was slow this time, so the alert keeps its state until there's a value to compare
+                None => continue,
+            };
+
+            let previous_state = status.state;
+            if matches {
+                if status.state == AlertState::Inactive || status.state == AlertState::Resolved {
+                    status.state = AlertState::Pending;
+                    status.since = Some(now);
+                }
+
+                if let (AlertState::Pending, Some(since)) = (status.state, status.since) {
+                    if now - since >= Duration::seconds(status.rule.for_seconds as i64) {
+                        status.state = AlertState::Firing;
+                        status.since = Some(now);
+                    }
+                }
+            } else {
+                match status.state {
+                    AlertState::Pending => {
+                        status.state = AlertState::Inactive;
+                        status.since = Some(now);
+                    }
+                    AlertState::Firing => {
+                        status.state = AlertState::Resolved;
+                        status.since = Some(now);
+                    }
+                    AlertState::Inactive | AlertState::Resolved => (),
+                }
+            }
+
+            if status.state != previous_state {
+                changed_statuses.push(status.clone());
+            }
+        }
+
+        for status in &changed_statuses {
+            if status.state == AlertState::Firing || status.state == AlertState::Resolved {
+                for notifier in &self.notifiers {
+                    notifier.notify(AlertNotification {
+                        status: status.clone(),
+                        stats: stats.clone(),
+                    });
+                }
+            }
+        }
+
+        changed_statuses
+    }
+}
+
+/// Builds a description of the provided rule and the current value of its stat.
+fn build_message(rule: &AlertRule, value: Option<f64>) -> String {
+    let value_description = match value {
+        Some(x) => format!("{} is {:.2}", rule.metric, x),
+        None => format!("{} has no value", rule.metric),
+    };
+
+    format!(
+        "{}: {} (alerts when {} {} for {} seconds)",
+        rule.name,
+        value_description,
+        rule.comparator.symbol(),
+        rule.threshold,
+        rule.for_seconds
+    )
+}
+
+/// Gets the numeric value at the provided path in some serialized stats. Returns `None` if there is no numeric value at the path.
+///
+/// # Arguments
+/// * `stats` - The serialized stats.
+/// * `path` - The path to the value, with each part separated by a `.`. Parts that refer to an element of a list are the index of the element.
+fn get_stat_value(stats: &Value, path: &str) -> Option<f64> {
+    let mut value = stats;
+    for part in path.split('.') {
+        value = match value {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(list) => list.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    value.as_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::stats::CpuStats;
+
+    /// Records the state of each alert it's notified about.
+    struct RecordingNotifier(Arc<Mutex<Vec<AlertState>>>);
+
+    impl AlertNotifier for RecordingNotifier {
+        fn notify(&self, notification: AlertNotification) {
+            self.0.lock().unwrap().push(notification.status.state);
+        }
+    }
+
+    fn stats_with_cpu_load(load: Option<f32>, seconds: i64) -> AllStats {
+        let mut stats = AllStats::empty(Local::now() + Duration::seconds(seconds));
+        stats.cpu = Some(CpuStats {
+            aggregate_load_percent: load,
+            ..CpuStats::default()
+        });
+        stats
+    }
+
+    #[test]
+    fn missing_value_keeps_alert_state() {
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let rule = AlertRule {
+            name: "High CPU usage".to_string(),
+            metric: "cpu.aggregateLoadPercent".to_string(),
+            comparator: Comparator::GreaterThan,
+            threshold: 90.0,
+            for_seconds: 10,
+        };
+        let mut alerts = Alerts::new(
+            vec![rule],
+            vec![Box::new(RecordingNotifier(Arc::clone(&notifications)))],
+        );
+
+        alerts.evaluate(&stats_with_cpu_load(Some(95.0), 0));
+        assert!(alerts.evaluate(&stats_with_cpu_load(None, 5)).is_empty());
+        assert_eq!(AlertState::Pending, alerts.statuses()[0].state);
+        assert_eq!(None, alerts.statuses()[0].value);
+
+        // the missing value doesn't reset how long the stat has matched the rule
+        alerts.evaluate(&stats_with_cpu_load(Some(95.0), 10));
+        assert_eq!(AlertState::Firing, alerts.statuses()[0].state);
+
+        assert!(alerts.evaluate(&stats_with_cpu_load(None, 15)).is_empty());
+        assert_eq!(AlertState::Firing, alerts.statuses()[0].state);
+
+        alerts.evaluate(&stats_with_cpu_load(Some(50.0), 20));
+        assert_eq!(AlertState::Resolved, alerts.statuses()[0].state);
+        assert_eq!(
+            vec![AlertState::Firing, AlertState::Resolved],
+            *notifications.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! Error responses for the API.
+
+use rocket::{http::Status, response::status::Custom, serde::json::Json};
+use serde::Serialize;
+
+/// The body of an error response from the API.
+#[derive(Serialize)]
+pub struct ApiError {
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+/// A response for an API request that failed.
+pub type ApiErrorResponse = Custom<Json<ApiError>>;
+
+impl ApiError {
+    /// Builds a response with the provided status and error message.
+    pub fn response(status: Status, message: String) -> ApiErrorResponse {
+        Custom(status, Json(ApiError { message }))
+    }
+}
@@ -0,0 +1,435 @@
+//! Stats for control groups.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    io::{self, Error},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collectors::CollectionKind;
+use crate::stats::log;
+
+const DEFAULT_CGROUP_DIR: &str = "/sys/fs/cgroup";
+
+/// The file that only exists at the root of a cgroup v2 hierarchy.
+const CONTROLLERS_FILE_NAME: &str = "cgroup.controllers";
+
+/// The value used in cgroup limit files when there is no limit.
+const NO_LIMIT: &str = "max";
+
+const MICROSECONDS_PER_SECOND: f32 = 1_000_000.0;
+
+/// Stats for a single control group
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CgroupStats {
+    /// The path of the cgroup, relative to the root of the cgroup hierarchy
+    pub name: String,
+    /// Percentage of a single logical CPU used by the cgroup since the previous collection. Can exceed 100 for cgroups using more than one logical CPU.
+    pub cpu_percent: f32,
+    /// Total microseconds of CPU time used by the cgroup
+    pub cpu_usage_microseconds: u64,
+    /// Bytes of memory currently used by the cgroup
+    pub memory_current_bytes: Option<u64>,
+    /// The memory limit of the cgroup in bytes, if it has one
+    pub memory_max_bytes: Option<u64>,
+    /// Total bytes read from block devices by the cgroup
+    pub io_read_bytes: Option<u64>,
+    /// Total bytes written to block devices by the cgroup
+    pub io_written_bytes: Option<u64>,
+    /// Number of processes and threads currently in the cgroup
+    pub pids_current: Option<u64>,
+    /// The limit on the number of processes and threads in the cgroup, if it has one
+    pub pids_max: Option<u64>,
+}
+
+/// How to sort a list of cgroup stats.
+#[derive(Debug, Clone, Copy)]
+pub enum CgroupSortOrder {
+    /// Highest CPU usage first.
+    Cpu,
+    /// Highest memory usage first.
+    Memory,
+    /// Most bytes read and written first.
+    Io,
+    /// Most processes and threads first.
+    Pids,
+    /// Alphabetically by name.
+    Name,
+}
+
+impl CgroupSortOrder {
+    /// Parses a sort order from its name. Returns `None` if the name isn't recognized.
+    pub fn parse(name: &str) -> Option<CgroupSortOrder> {
+        match name {
+            "cpu" => Some(CgroupSortOrder::Cpu),
+            "memory" => Some(CgroupSortOrder::Memory),
+            "io" => Some(CgroupSortOrder::Io),
+            "pids" => Some(CgroupSortOrder::Pids),
+            "name" => Some(CgroupSortOrder::Name),
+            _ => None,
+        }
+    }
+
+    /// Sorts the provided list of cgroup stats.
+    pub fn sort(self, cgroups: &mut [CgroupStats]) {
+        match self {
+            CgroupSortOrder::Cpu => cgroups.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent)),
+            CgroupSortOrder::Memory => cgroups.sort_by_key(|x| Reverse(x.memory_current_bytes)),
+            CgroupSortOrder::Io => cgroups.sort_by_key(|x| {
+                Reverse(x.io_read_bytes.unwrap_or(0) + x.io_written_bytes.unwrap_or(0))
+            }),
+            CgroupSortOrder::Pids => cgroups.sort_by_key(|x| Reverse(x.pids_current)),
+            CgroupSortOrder::Name => cgroups.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+}
+
+/// Collects stats for each cgroup in a cgroup v2 hierarchy. CPU usage is calculated from the CPU time each cgroup used since the previous scheduled collection.
+pub struct CgroupCollector {
+    /// The root of the cgroup hierarchy.
+    cgroup_dir: PathBuf,
+    /// The total CPU microseconds used by each cgroup as of the previous scheduled collection, and the time of that collection, if there's been one.
+    previous_collection: Mutex<Option<(HashMap<String, u64>, Instant)>>,
+}
+
+impl CgroupCollector {
+    /// Creates a `CgroupCollector` that reads from `/sys/fs/cgroup`.
+    pub fn new() -> CgroupCollector {
+        CgroupCollector::with_cgroup_dir(DEFAULT_CGROUP_DIR.into())
+    }
+
+    /// Creates a `CgroupCollector` that reads from the provided directory instead of `/sys/fs/cgroup`.
+    pub fn with_cgroup_dir(cgroup_dir: PathBuf) -> CgroupCollector {
+        CgroupCollector {
+            cgroup_dir,
+            previous_collection: Mutex::new(None),
+        }
+    }
+
+    /// Gets stats for each cgroup below the root of the hierarchy. Returns `None` if the directory isn't a cgroup v2 hierarchy or an error occurs. Only scheduled collections replace the CPU usage later collections are compared to.
+    pub fn collect_cgroups(&self, kind: CollectionKind) -> Option<Vec<CgroupStats>> {
+        if !self.cgroup_dir.join(CONTROLLERS_FILE_NAME).exists() {
+            log("Error getting cgroups: ", Error::other("Not supported"));
+            return None;
+        }
+
+        let mut cgroup_dirs = Vec::new();
+        if let Err(e) = find_cgroups(&self.cgroup_dir, &mut cgroup_dirs) {
+            log("Error getting cgroups: ", e);
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut previous_collection = self.previous_collection.lock().unwrap();
+        let empty_cpu_usage = HashMap::new();
+        let (previous_cpu_usage, elapsed_seconds) = match &*previous_collection {
+            Some((cpu_usage, time)) => (cpu_usage, Some(now.duration_since(*time).as_secs_f32())),
+            None => (&empty_cpu_usage, None),
+        };
+
+        let mut cgroups = Vec::new();
+        let mut cpu_usage = HashMap::new();
+        for dir in cgroup_dirs {
+            let name = match dir.strip_prefix(&self.cgroup_dir) {
+                Ok(x) => x.to_string_lossy().to_string(),
+                Err(_) => continue,
+            };
+            // cgroups can be removed at any time, so errors reading them are expected and ignored
+            if let Ok(usage) = read_cpu_usage(&dir) {
+                let cpu_percent = match (previous_cpu_usage.get(&name), elapsed_seconds) {
+                    (Some(previous_usage), Some(elapsed)) if elapsed > 0.0 => {
+                        (usage.saturating_sub(*previous_usage) as f32 / MICROSECONDS_PER_SECOND)
+                            / elapsed
+                            * 100.0
+                    }
+                    _ => 0.0,
+                };
+                let (io_read_bytes, io_written_bytes) = match read_io_bytes(&dir) {
+                    Some((read, written)) => (Some(read), Some(written)),
+                    None => (None, None),
+                };
+                cpu_usage.insert(name.clone(), usage);
+                cgroups.push(CgroupStats {
+                    name,
+                    cpu_percent,
+                    cpu_usage_microseconds: usage,
+                    memory_current_bytes: read_number(&dir.join("memory.current")),
+                    memory_max_bytes: read_number(&dir.join("memory.max")),
+                    io_read_bytes,
+                    io_written_bytes,
+                    pids_current: read_number(&dir.join("pids.current")),
+                    pids_max: read_number(&dir.join("pids.max")),
+                });
+            }
+        }
+
+        if kind == CollectionKind::Scheduled {
+            *previous_collection = Some((cpu_usage, now));
+        }
+
+        Some(cgroups)
+    }
+}
+
+impl Default for CgroupCollector {
+    fn default() -> Self {
+        CgroupCollector::new()
+    }
+}
+
+/// Adds the directories of all the cgroups below the provided one to the provided list, sorted so parents come before their children.
+fn find_cgroups(dir: &Path, cgroup_dirs: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut children = read_dir(dir)?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    children.retain(|x| x.is_dir());
+    children.sort();
+    for child in children {
+        cgroup_dirs.push(child.clone());
+        // cgroups can be removed at any time, so errors finding the children of one are ignored
+        let _ = find_cgroups(&child, cgroup_dirs);
+    }
+
+    Ok(())
+}
+
+/// Reads the total CPU microseconds used by a cgroup from its `cpu.stat` file.
+fn read_cpu_usage(dir: &Path) -> io::Result<u64> {
+    let cpu_stat = read_to_string(dir.join("cpu.stat"))?;
+    cpu_stat
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(key, _)| *key == "usage_usec")
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing usage_usec"))
+}
+
+/// Reads the total bytes read and written by a cgroup across all devices from its `io.stat` file. Returns `None` if the file can't be read, like when the I/O controller isn't enabled for the cgroup.
+fn read_io_bytes(dir: &Path) -> Option<(u64, u64)> {
+    let io_stat = read_to_string(dir.join("io.stat")).ok()?;
+    let mut read_bytes = 0;
+    let mut written_bytes = 0;
+    // each line is a device number followed by key=value pairs
+    for (key, value) in io_stat.split_whitespace().filter_map(|x| x.split_once('=')) {
+        let value: u64 = value.parse().unwrap_or(0);
+        match key {
+            "rbytes" => read_bytes += value,
+            "wbytes" => written_bytes += value,
+            _ => (),
+        }
+    }
+
+    Some((read_bytes, written_bytes))
+}
+
+/// Reads a number from a cgroup file. Returns `None` if the file can't be read, like when its controller isn't enabled for the cgroup, or if it contains `max` because there is no limit.
+fn read_number(path: &Path) -> Option<u64> {
+    let contents = read_to_string(path).ok()?;
+    let trimmed = contents.trim();
+    if trimmed == NO_LIMIT {
+        None
+    } else {
+        trimmed.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Writes the files for a cgroup in the provided hierarchy.
+    fn write_cgroup(root: &Path, name: &str, files: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (file_name, contents) in files {
+            fs::write(dir.join(file_name), contents).unwrap();
+        }
+    }
+
+    /// Creates a cgroup v2 hierarchy with a couple of cgroups in it.
+    fn create_hierarchy() -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(CONTROLLERS_FILE_NAME),
+            "cpu io memory pids\n",
+        )
+        .unwrap();
+        write_cgroup(
+            root.path(),
+            "system.slice",
+            &[
+                ("cpu.stat", "usage_usec 1000000\nuser_usec 600000\nsystem_usec 400000\n"),
+                ("memory.current", "2000000\n"),
+                ("memory.max", "max\n"),
+                ("io.stat", "8:0 rbytes=1000 wbytes=2000 rios=1 wios=2\n8:16 rbytes=300 wbytes=400 rios=3 wios=4\n"),
+                ("pids.current", "12\n"),
+                ("pids.max", "max\n"),
+            ],
+        );
+        write_cgroup(
+            root.path(),
+            "system.slice/sshd.service",
+            &[
+                ("cpu.stat", "usage_usec 250000\n"),
+                ("memory.current", "5000000\n"),
+                ("memory.max", "8000000\n"),
+                ("pids.current", "3\n"),
+                ("pids.max", "100\n"),
+            ],
+        );
+
+        root
+    }
+
+    #[test]
+    fn collect_parses_cgroup_files() {
+        let root = create_hierarchy();
+        let cgroups = CgroupCollector::with_cgroup_dir(root.path().to_path_buf())
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+
+        assert_eq!(2, cgroups.len());
+        let slice = &cgroups[0];
+        assert_eq!("system.slice", slice.name);
+        assert_eq!(0.0, slice.cpu_percent);
+        assert_eq!(1_000_000, slice.cpu_usage_microseconds);
+        assert_eq!(Some(2_000_000), slice.memory_current_bytes);
+        assert_eq!(None, slice.memory_max_bytes);
+        assert_eq!(Some(1300), slice.io_read_bytes);
+        assert_eq!(Some(2400), slice.io_written_bytes);
+        assert_eq!(Some(12), slice.pids_current);
+        assert_eq!(None, slice.pids_max);
+
+        let service = &cgroups[1];
+        assert_eq!("system.slice/sshd.service", service.name);
+        assert_eq!(250_000, service.cpu_usage_microseconds);
+        assert_eq!(Some(5_000_000), service.memory_current_bytes);
+        assert_eq!(Some(8_000_000), service.memory_max_bytes);
+        assert_eq!(None, service.io_read_bytes);
+        assert_eq!(None, service.io_written_bytes);
+        assert_eq!(Some(3), service.pids_current);
+        assert_eq!(Some(100), service.pids_max);
+    }
+
+    #[test]
+    fn collect_calculates_cpu_percent_from_previous_collection() {
+        let root = create_hierarchy();
+        let collector = CgroupCollector::with_cgroup_dir(root.path().to_path_buf());
+        let start = Instant::now();
+        collector
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+
+        thread::sleep(Duration::from_millis(100));
+        write_cgroup(
+            root.path(),
+            "system.slice",
+            &[("cpu.stat", "usage_usec 1050000\n")],
+        );
+        let cgroups = collector
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+        let elapsed = start.elapsed().as_secs_f32();
+
+        // 0.05 seconds of CPU time were used in at least 0.1 seconds, and at most the time the test took
+        let cpu_percent = cgroups[0].cpu_percent;
+        assert!(cpu_percent <= 50.0, "{}", cpu_percent);
+        assert!(cpu_percent >= 5.0 / elapsed, "{}", cpu_percent);
+        assert_eq!(0.0, cgroups[1].cpu_percent);
+    }
+
+    #[test]
+    fn fresh_collection_keeps_previous_scheduled_collection() {
+        let root = create_hierarchy();
+        let collector = CgroupCollector::with_cgroup_dir(root.path().to_path_buf());
+        let start = Instant::now();
+        collector
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+
+        thread::sleep(Duration::from_millis(100));
+        write_cgroup(
+            root.path(),
+            "system.slice",
+            &[("cpu.stat", "usage_usec 1050000\n")],
+        );
+        let fresh = collector.collect_cgroups(CollectionKind::Fresh).unwrap();
+        assert!(fresh[0].cpu_percent > 0.0, "{}", fresh[0].cpu_percent);
+
+        thread::sleep(Duration::from_millis(100));
+        let cgroups = collector
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+        let elapsed = start.elapsed().as_secs_f32();
+
+        // the CPU time used before the fresh collection still counts, over the whole time since the first collection
+        let cpu_percent = cgroups[0].cpu_percent;
+        assert!(cpu_percent <= 25.0, "{}", cpu_percent);
+        assert!(cpu_percent >= 5.0 / elapsed, "{}", cpu_percent);
+    }
+
+    #[test]
+    fn collect_skips_cgroups_without_cpu_stats() {
+        let root = create_hierarchy();
+        write_cgroup(root.path(), "init.scope", &[("memory.current", "1000\n")]);
+        let cgroups = CgroupCollector::with_cgroup_dir(root.path().to_path_buf())
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+
+        let names: Vec<&str> = cgroups.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(vec!["system.slice", "system.slice/sshd.service"], names);
+    }
+
+    #[test]
+    fn collect_returns_none_for_non_v2_hierarchy() {
+        let root = create_hierarchy();
+        fs::remove_file(root.path().join(CONTROLLERS_FILE_NAME)).unwrap();
+
+        assert!(CgroupCollector::with_cgroup_dir(root.path().to_path_buf())
+            .collect_cgroups(CollectionKind::Scheduled)
+            .is_none());
+    }
+
+    #[test]
+    fn sort_orders_cgroups() {
+        let root = create_hierarchy();
+        let mut cgroups = CgroupCollector::with_cgroup_dir(root.path().to_path_buf())
+            .collect_cgroups(CollectionKind::Scheduled)
+            .unwrap();
+        let names = |cgroups: &[CgroupStats]| -> Vec<String> {
+            cgroups.iter().map(|x| x.name.clone()).collect()
+        };
+
+        CgroupSortOrder::Memory.sort(&mut cgroups);
+        assert_eq!(
+            vec!["system.slice/sshd.service", "system.slice"],
+            names(&cgroups)
+        );
+        CgroupSortOrder::Io.sort(&mut cgroups);
+        assert_eq!(
+            vec!["system.slice", "system.slice/sshd.service"],
+            names(&cgroups)
+        );
+        CgroupSortOrder::Name.sort(&mut cgroups);
+        assert_eq!(
+            vec!["system.slice", "system.slice/sshd.service"],
+            names(&cgroups)
+        );
+        CgroupSortOrder::Pids.sort(&mut cgroups);
+        assert_eq!(
+            vec!["system.slice", "system.slice/sshd.service"],
+            names(&cgroups)
+        );
+    }
+}
@@ -0,0 +1,918 @@
+//! Collectors that each gather one group of stats, and the registry of collectors that are enabled.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use systemstat::{Platform, System};
+
+use crate::cgroups::CgroupCollector;
+use crate::consolidation::*;
+use crate::dashboard_context::*;
+use crate::filters::{InterfaceFilter, MountFilter, StatsFilters};
+use crate::processes::ProcessCollector;
+use crate::sensors::SensorStats;
+use crate::stats::*;
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+/// The number of intervals a collector's stats can go without being updated before they're considered stale.
+const STALE_INTERVALS: u32 = 3;
+
+/// Gathers one group of stats, which are stored under the collector's own key in `AllStats`. The field is `None`, and left out when serialized, if the collector is disabled or couldn't collect its stats. Each collector runs on its own thread, so anything it keeps between collections must be safe to share.
+pub trait Collector: Send + Sync {
+    /// The name of this collector. This is the key its stats are stored under, and is used to configure it.
+    fn name(&self) -> &'static str;
+
+    /// Collects this collector's stats from the provided system, and stores them in the provided stats. Rates and usage calculated since the previous collection are only calculated since the previous scheduled collection, and only scheduled collections replace it, so fresh collections don't shorten the period the next scheduled one covers.
+    fn collect(&self, sys: &System, stats: &mut AllStats, kind: CollectionKind);
+
+    /// Copies this collector's stats from `collected`, which holds stats it collected, into `stats`.
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats);
+
+    /// Consolidates this collector's stats from each of the provided stats, and stores them in `consolidated`.
+    ///
+    /// # Arguments
+    /// * `stats_list` - The stats to consolidate, from oldest to newest. Never empty.
+    /// * `strategy` - How to consolidate the stats.
+    /// * `consolidated` - The stats to store the consolidated stats in.
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    );
+
+    /// Builds dashboard charts for this collector's stats. Builds no charts by default.
+    ///
+    /// # Arguments
+    /// * `entries` - The entries to chart, from oldest to newest. `None` entries are gaps.
+    /// * `most_recent_stats` - The most recently collected stats.
+    /// * `dark_mode` - Whether dark mode is enabled or not.
+    fn build_charts(
+        &self,
+        _entries: &[Option<&AllStats>],
+        _most_recent_stats: &AllStats,
+        _dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        Vec::new()
+    }
+
+    /// Builds dashboard sections for this collector's most recently collected stats. Builds no sections by default.
+    fn build_sections(&self, _most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        Vec::new()
+    }
+
+    /// Builds dashboard tables for this collector's most recently collected stats. Builds no tables by default.
+    fn build_tables(&self, _most_recent_stats: &AllStats) -> Vec<TableContext> {
+        Vec::new()
+    }
+}
+
+/// Why stats are being collected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollectionKind {
+    /// The collector's regular collection, every interval.
+    Scheduled,
+    /// An extra collection requested with `collect_now`.
+    Fresh,
+}
+
+/// Configuration for a single collector. Either just whether it's enabled, or a table of options.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CollectorConfig {
+    /// Whether the collector is enabled.
+    Enabled(bool),
+    /// Options for the collector.
+    Options {
+        /// Whether the collector is enabled.
+        #[serde(default = "default_enabled")]
+        enabled: bool,
+        /// How often the collector collects stats. Defaults to the update frequency.
+        interval_seconds: Option<u64>,
+        /// How long a collection can take before the collector's stats are reported as timed out.
+        timeout_seconds: Option<u64>,
+    },
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl CollectorConfig {
+    /// Whether the collector is enabled.
+    fn enabled(&self) -> bool {
+        match self {
+            CollectorConfig::Enabled(x) => *x,
+            CollectorConfig::Options { enabled, .. } => *enabled,
+        }
+    }
+
+    /// How often the collector collects stats, if it's configured.
+    fn interval(&self) -> Option<Duration> {
+        match self {
+            CollectorConfig::Enabled(_) => None,
+            CollectorConfig::Options {
+                interval_seconds, ..
+            } => interval_seconds.map(Duration::from_secs),
+        }
+    }
+
+    /// How long a collection can take before it's reported as timed out, if it's configured.
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            CollectorConfig::Enabled(_) => None,
+            CollectorConfig::Options {
+                timeout_seconds, ..
+            } => timeout_seconds.map(Duration::from_secs),
+        }
+    }
+}
+
+/// The collectors that are enabled, in the order their charts and sections are displayed. Each collector collects stats on its own thread at its own interval, so a slow one doesn't delay the others.
+pub struct CollectorRegistry {
+    /// The enabled collectors.
+    collectors: Vec<RunningCollector>,
+    /// The shortest amount of time to allow between collections requested with `collect_now`.
+    min_fresh_interval: Duration,
+    /// The stats from the most recent collection requested with `collect_now`, if there's been one. Locked for the whole of each `collect_now` call, so concurrent calls share a single collection.
+    last_fresh_stats: Mutex<Option<AllStats>>,
+    /// Whether to include the 95th percentile of each stat when consolidating stats.
+    include_p95: bool,
+}
+
+/// A collector that's collecting stats on its own thread.
+#[derive(Clone)]
+struct RunningCollector {
+    /// The collector.
+    collector: Arc<dyn Collector>,
+    /// How often the collector collects stats.
+    interval: Duration,
+    /// How long a collection can take before it's reported as timed out.
+    timeout: Duration,
+    /// The state of the collector's collections, shared with its thread.
+    state: Arc<Mutex<CollectionState>>,
+    /// Notified when a collection is requested, and when a collection finishes.
+    signal: Arc<Condvar>,
+}
+
+/// The state of a collector's collections.
+#[derive(Default)]
+struct CollectionState {
+    /// The stats from the most recent scheduled collection, if there's been one.
+    latest: Option<AllStats>,
+    /// The stats from the most recent fresh collection, if there's been one.
+    latest_fresh: Option<AllStats>,
+    /// When the collection in progress started, if there is one.
+    collecting_since: Option<Instant>,
+    /// How long the most recent collection took.
+    last_duration: Duration,
+    /// The number of collections that have finished.
+    finished_collections: u64,
+    /// Whether a collection has been requested before the next one is due.
+    collection_requested: bool,
+}
+
+impl CollectorRegistry {
+    /// Creates a `CollectorRegistry` with each of the built-in collectors that are enabled, and starts them collecting stats.
+    ///
+    /// # Arguments
+    /// * `configs` - The configuration for each collector, by name. Collectors that aren't included are enabled, with the default interval.
+    /// * `default_interval` - How often collectors collect stats if they don't have an interval configured.
+    /// * `min_fresh_interval` - The shortest amount of time to allow between collections requested with `collect_now`.
+    /// * `filters` - The filters to choose which mounts and network interfaces to include.
+    /// * `include_p95` - Whether to include the 95th percentile of each stat when consolidating stats.
+    pub fn new(
+        configs: &BTreeMap<String, CollectorConfig>,
+        default_interval: Duration,
+        min_fresh_interval: Duration,
+        filters: &StatsFilters,
+        include_p95: bool,
+    ) -> CollectorRegistry {
+        let all_collectors: Vec<Arc<dyn Collector>> = vec![
+            Arc::new(CpuCollector::default()),
+            Arc::new(MemoryCollector),
+            Arc::new(GeneralCollector),
+            Arc::new(FilesystemCollector::new(filters.mounts.clone())),
+            Arc::new(DiskCollector::default()),
+            Arc::new(SensorCollector),
+            Arc::new(NetworkCollector::new(filters.interfaces.clone())),
+            Arc::new(ProcessCollector::new()),
+            Arc::new(CgroupCollector::new()),
+        ];
+
+        for name in configs.keys() {
+            if !all_collectors.iter().any(|x| x.name() == name) {
+                println!("Ignoring configuration for unknown collector {}", name);
+            }
+        }
+
+        let mut collectors = Vec::new();
+        for collector in all_collectors {
+            let config = configs.get(collector.name());
+            if !config.map(|x| x.enabled()).unwrap_or(true) {
+                println!("The {} collector is disabled", collector.name());
+                continue;
+            }
+
+            let running = RunningCollector {
+                collector,
+                interval: config
+                    .and_then(|x| x.interval())
+                    .unwrap_or(default_interval),
+                timeout: config
+                    .and_then(|x| x.timeout())
+                    .unwrap_or_else(|| Duration::from_secs(DEFAULT_TIMEOUT_SECONDS)),
+                state: Arc::new(Mutex::new(CollectionState::default())),
+                signal: Arc::new(Condvar::new()),
+            };
+            let thread_running = running.clone();
+            thread::spawn(move || thread_running.run());
+            collectors.push(running);
+        }
+
+        CollectorRegistry {
+            collectors,
+            min_fresh_interval,
+            last_fresh_stats: Mutex::new(None),
+            include_p95,
+        }
+    }
+
+    /// Assembles stats from the most recent collection of each enabled collector, along with how old each of them is. Stats for disabled collectors and collectors that haven't collected anything yet are left empty. So are stats for collectors that timed out or haven't collected anything for `STALE_INTERVALS` intervals, so stale stats don't end up in the history, but their status is still included. Alerts keep their state for stats left empty this way, rather than treating them as no longer matching.
+    pub fn latest(&self) -> AllStats {
+        self.assemble(|state| state.latest.as_ref())
+    }
+
+    /// Assembles stats from the collection `choose` picks for each enabled collector, the same way as `latest`.
+    fn assemble(&self, choose: impl Fn(&CollectionState) -> Option<&AllStats>) -> AllStats {
+        let now = Local::now();
+        let mut stats = AllStats::empty(now);
+        for running in &self.collectors {
+            let state = running.state.lock().unwrap();
+            let latest = match choose(&state) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let timed_out = match state.collecting_since {
+                Some(x) => x.elapsed() > running.timeout,
+                None => false,
+            } || state.last_duration > running.timeout;
+            let age_seconds = seconds_between(latest.collection_time, now);
+            let stale = age_seconds > (running.interval * STALE_INTERVALS).as_secs_f32();
+            if !timed_out && !stale {
+                running.collector.merge(latest, &mut stats);
+            }
+            stats.collectors.insert(
+                running.collector.name().to_string(),
+                CollectorStatus {
+                    collection_time: latest.collection_time,
+                    age_seconds,
+                    timed_out,
+                },
+            );
+        }
+
+        stats
+    }
+
+    /// Has each enabled collector's thread collect stats right away rather than waiting for its next collection, waits for them to finish, and assembles them like `latest`. Collectors that are already in the middle of a collection are left to finish it. Collectors that don't finish within their timeout have their most recent stats used, unless they're left out like in `latest`. Blocks until the collections finish, so it shouldn't be called from async code.
+    ///
+    /// Fresh collections don't change the stats `latest` returns, so they don't end up in the history, and don't disturb the rates calculated by the scheduled collections. To keep frequent requests from overloading the system, if this was called less than `min_fresh_interval` ago, the stats from that call are returned instead. Calls made while a collection is in progress wait for it and share its stats.
+    pub fn collect_now(&self) -> AllStats {
+        let mut last_fresh_stats = self.last_fresh_stats.lock().unwrap();
+        if let Some(x) = &*last_fresh_stats {
+            let since_last = Local::now() - x.collection_time;
+            if since_last.to_std().unwrap_or_default() < self.min_fresh_interval {
+                return x.clone();
+            }
+        }
+
+        let start = Instant::now();
+        let requested: Vec<u64> = self
+            .collectors
+            .iter()
+            .map(|running| running.request_collection())
+            .collect();
+        for (running, finished_collections) in self.collectors.iter().zip(requested) {
+            let remaining = running.timeout.saturating_sub(start.elapsed());
+            running.wait_for_collection(finished_collections, remaining);
+        }
+
+        // a scheduled collection that was in progress can satisfy the request instead of a fresh one
+        let stats = self.assemble(|state| match (&state.latest, &state.latest_fresh) {
+            (Some(scheduled), Some(fresh)) if fresh.collection_time > scheduled.collection_time => {
+                Some(fresh)
+            }
+            (scheduled, fresh) => scheduled.as_ref().or(fresh.as_ref()),
+        });
+        *last_fresh_stats = Some(stats.clone());
+        stats
+    }
+
+    /// Consolidates the provided stats into a single entry, with the collection time and collector statuses of the most recent one. The entry holds the average of each stat, along with the other ways of consolidating them. Entries that were already consolidated are consolidated by using the matching way they were consolidated, so they can be consolidated again.
+    ///
+    /// # Panics
+    /// Panics if `stats_list` is empty.
+    pub fn consolidate(&self, stats_list: &[AllStats]) -> AllStats {
+        let mut consolidated = self.consolidate_with(stats_list, ConsolidationStrategy::Average);
+        consolidated.consolidated = Some(Box::new(ConsolidatedStats {
+            min: self.consolidate_other_way(stats_list, ConsolidationStrategy::Min),
+            max: self.consolidate_other_way(stats_list, ConsolidationStrategy::Max),
+            p95: if self.include_p95 {
+                Some(self.consolidate_other_way(stats_list, ConsolidationStrategy::P95))
+            } else {
+                None
+            },
+            last: self.consolidate_other_way(stats_list, ConsolidationStrategy::Last),
+        }));
+
+        consolidated
+    }
+
+    /// Consolidates the provided stats into a single entry with the provided strategy, to store alongside the averaged entry. Stats that are the same however they're consolidated are left out.
+    fn consolidate_other_way(
+        &self,
+        stats_list: &[AllStats],
+        strategy: ConsolidationStrategy,
+    ) -> AllStats {
+        let mut consolidated = self.consolidate_with(stats_list, strategy);
+        clear_unconsolidated(&mut consolidated);
+        consolidated
+    }
+
+    /// Consolidates the provided stats into a single entry with the provided strategy.
+    fn consolidate_with(
+        &self,
+        stats_list: &[AllStats],
+        strategy: ConsolidationStrategy,
+    ) -> AllStats {
+        let last_stats = stats_list.last().expect("stats_list must not be empty");
+        let stats_list: Vec<&AllStats> = stats_list
+            .iter()
+            .map(|x| x.consolidated_with(strategy))
+            .collect();
+        let mut consolidated = AllStats::empty(last_stats.collection_time);
+        consolidated.collectors = last_stats.collectors.clone();
+        for running in &self.collectors {
+            running
+                .collector
+                .consolidate(&stats_list, strategy, &mut consolidated);
+        }
+
+        consolidated
+    }
+
+    /// Builds the dashboard charts for each enabled collector.
+    pub fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        self.collectors
+            .iter()
+            .flat_map(|x| {
+                x.collector
+                    .build_charts(entries, most_recent_stats, dark_mode)
+            })
+            .collect()
+    }
+
+    /// Builds the dashboard sections for each enabled collector.
+    pub fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        self.collectors
+            .iter()
+            .flat_map(|x| x.collector.build_sections(most_recent_stats))
+            .collect()
+    }
+
+    /// Builds the dashboard tables for each enabled collector.
+    pub fn build_tables(&self, most_recent_stats: &AllStats) -> Vec<TableContext> {
+        self.collectors
+            .iter()
+            .flat_map(|x| x.collector.build_tables(most_recent_stats))
+            .collect()
+    }
+}
+
+impl RunningCollector {
+    /// Collects stats every `interval` forever, along with fresh collections whenever they're requested in between.
+    fn run(self) {
+        let system = System::new();
+        loop {
+            let start = Instant::now();
+            self.collect(&system, CollectionKind::Scheduled);
+
+            loop {
+                let state = self.state.lock().unwrap();
+                let (mut state, _) = self
+                    .signal
+                    .wait_timeout_while(state, self.interval.saturating_sub(start.elapsed()), |x| {
+                        !x.collection_requested
+                    })
+                    .unwrap();
+                if !state.collection_requested {
+                    break;
+                }
+
+                state.collection_requested = false;
+                drop(state);
+                self.collect(&system, CollectionKind::Fresh);
+            }
+        }
+    }
+
+    /// Requests a collection from the collector's thread. Returns the number of collections that had finished beforehand.
+    fn request_collection(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        // a collection in progress will satisfy the request once it finishes
+        if state.collecting_since.is_none() {
+            state.collection_requested = true;
+            self.signal.notify_all();
+        }
+
+        state.finished_collections
+    }
+
+    /// Waits up to `timeout` for a collection to finish after `finished_collections` collections had finished.
+    fn wait_for_collection(&self, finished_collections: u64, timeout: Duration) {
+        let state = self.state.lock().unwrap();
+        let _ = self
+            .signal
+            .wait_timeout_while(state, timeout, |x| {
+                x.finished_collections == finished_collections
+            })
+            .unwrap();
+    }
+
+    /// Collects stats and stores them as the most recent ones of their kind.
+    fn collect(&self, system: &System, kind: CollectionKind) {
+        let start = Instant::now();
+        self.state.lock().unwrap().collecting_since = Some(start);
+
+        let mut stats = AllStats::empty(Local::now());
+        self.collector.collect(system, &mut stats, kind);
+        let duration = start.elapsed();
+        if duration > self.timeout {
+            println!(
+                "Collecting {} stats took {:?}, which is longer than the timeout of {:?}",
+                self.collector.name(),
+                duration,
+                self.timeout
+            );
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match kind {
+            CollectionKind::Scheduled => state.latest = Some(stats),
+            CollectionKind::Fresh => state.latest_fresh = Some(stats),
+        }
+        state.collecting_since = None;
+        state.last_duration = duration;
+        state.finished_collections += 1;
+        self.signal.notify_all();
+    }
+}
+
+/// Collects uptime, load averages, and pressure stall information.
+struct GeneralCollector;
+
+impl Collector for GeneralCollector {
+    fn name(&self) -> &'static str {
+        "general"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, _kind: CollectionKind) {
+        stats.general = Some(GeneralStats::from(sys));
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.general = collected.general.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        consolidated.general =
+            consolidate_optional_field(stats_list, strategy, |x| x.general.as_ref());
+    }
+
+    fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        _dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        let mut charts = vec![build_load_average_chart(entries)];
+        if most_recent_stats
+            .general
+            .as_ref()
+            .and_then(|x| x.pressure.as_ref())
+            .is_some()
+        {
+            charts.push(build_pressure_chart(entries));
+        }
+
+        charts
+    }
+
+    fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        most_recent_stats
+            .general
+            .iter()
+            .filter_map(build_general_section)
+            .collect()
+    }
+}
+
+/// Collects CPU load, time breakdowns, and temperature. Load is calculated from the CPU time used since the previous collection.
+#[derive(Default)]
+struct CpuCollector {
+    /// The CPU times from the previous collection.
+    previous_snapshot: Mutex<Option<CpuSnapshot>>,
+}
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, kind: CollectionKind) {
+        let mut previous_snapshot = self.previous_snapshot.lock().unwrap();
+        stats.cpu = Some(match kind {
+            CollectionKind::Scheduled => CpuStats::from(sys, &mut previous_snapshot),
+            CollectionKind::Fresh => CpuStats::from(sys, &mut previous_snapshot.clone()),
+        });
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.cpu = collected.cpu.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        consolidated.cpu = consolidate_optional_field(stats_list, strategy, |x| x.cpu.as_ref());
+    }
+
+    fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        let mut charts = build_cpu_charts(entries, dark_mode);
+        charts.push(build_cpu_breakdown_chart(entries));
+        charts.push(build_temperature_chart(entries, most_recent_stats));
+
+        charts
+    }
+}
+
+/// Collects memory and swap usage.
+struct MemoryCollector;
+
+impl Collector for MemoryCollector {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, _kind: CollectionKind) {
+        stats.memory = MemoryStats::from(sys);
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.memory = collected.memory.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        consolidated.memory =
+            consolidate_optional_field(stats_list, strategy, |x| x.memory.as_ref());
+    }
+
+    fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        _dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        vec![build_memory_chart(entries, most_recent_stats)]
+    }
+}
+
+/// Collects usage for each mounted filesystem.
+struct FilesystemCollector {
+    /// The filter to choose which mounts to include.
+    filter: MountFilter,
+}
+
+impl FilesystemCollector {
+    fn new(filter: MountFilter) -> FilesystemCollector {
+        FilesystemCollector { filter }
+    }
+}
+
+impl Collector for FilesystemCollector {
+    fn name(&self) -> &'static str {
+        "filesystems"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, _kind: CollectionKind) {
+        stats.filesystems = MountStats::from(sys, &self.filter);
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.filesystems = collected.filesystems.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        let filesystems: Vec<&Vec<MountStats>> = stats_list
+            .iter()
+            .filter_map(|x| x.filesystems.as_ref())
+            .collect();
+        consolidated.filesystems =
+            consolidate_list(&filesystems, strategy, |x| x.mounted_on.clone());
+    }
+
+    fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        most_recent_stats
+            .filesystems
+            .iter()
+            .map(|x| build_filesystems_section(x))
+            .collect()
+    }
+}
+
+/// Collects I/O stats for each block device. Rates are calculated from the I/O done since the previous collection.
+#[derive(Default)]
+struct DiskCollector {
+    /// The stats from the previous collection, and when they were collected.
+    previous: Mutex<Option<(Vec<DiskStats>, DateTime<Local>)>>,
+}
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &'static str {
+        "disks"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, kind: CollectionKind) {
+        let mut disks = DiskStats::from(sys);
+        let mut previous = self.previous.lock().unwrap();
+        if let (Some(disks), Some((previous_disks, previous_time))) = (&mut disks, &*previous) {
+            let elapsed_seconds = seconds_between(*previous_time, stats.collection_time);
+            if elapsed_seconds > 0.0 {
+                for disk in disks {
+                    if let Some(previous_disk) = previous_disks.iter().find(|x| x.name == disk.name)
+                    {
+                        disk.calculate_rates(previous_disk, elapsed_seconds);
+                    }
+                }
+            }
+        }
+
+        if kind == CollectionKind::Scheduled {
+            *previous = disks.clone().map(|x| (x, stats.collection_time));
+        }
+        stats.disks = disks;
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.disks = collected.disks.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        let disks: Vec<&Vec<DiskStats>> =
+            stats_list.iter().filter_map(|x| x.disks.as_ref()).collect();
+        consolidated.disks = consolidate_list(&disks, strategy, |x| x.name.clone());
+    }
+
+    fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        _dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        match &most_recent_stats.disks {
+            Some(x) => build_disk_io_charts(entries, x),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Collects temperatures and fan speeds from hardware sensors.
+struct SensorCollector;
+
+impl Collector for SensorCollector {
+    fn name(&self) -> &'static str {
+        "sensors"
+    }
+
+    fn collect(&self, _sys: &System, stats: &mut AllStats, _kind: CollectionKind) {
+        stats.sensors = SensorStats::read();
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.sensors = collected.sensors.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        consolidated.sensors =
+            consolidate_optional_field(stats_list, strategy, |x| x.sensors.as_ref());
+    }
+
+    fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        most_recent_stats
+            .sensors
+            .iter()
+            .filter_map(build_sensors_section)
+            .collect()
+    }
+}
+
+/// Collects stats for network interfaces and sockets. Throughput is calculated from the traffic since the previous collection.
+struct NetworkCollector {
+    /// The filter to choose which network interfaces to include.
+    filter: InterfaceFilter,
+    /// The stats from the previous collection, and when they were collected.
+    previous: Mutex<Option<(NetworkStats, DateTime<Local>)>>,
+}
+
+impl NetworkCollector {
+    fn new(filter: InterfaceFilter) -> NetworkCollector {
+        NetworkCollector {
+            filter,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+impl Collector for NetworkCollector {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn collect(&self, sys: &System, stats: &mut AllStats, kind: CollectionKind) {
+        let mut network = NetworkStats::from(sys, &self.filter);
+        let mut previous = self.previous.lock().unwrap();
+        if let Some((previous_network, previous_time)) = &*previous {
+            let elapsed_seconds = seconds_between(*previous_time, stats.collection_time);
+            if elapsed_seconds > 0.0 {
+                network.calculate_rates(previous_network, elapsed_seconds);
+            }
+        }
+
+        if kind == CollectionKind::Scheduled {
+            *previous = Some((network.clone(), stats.collection_time));
+        }
+        stats.network = Some(network);
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.network = collected.network.clone();
+    }
+
+    fn consolidate(
+        &self,
+        stats_list: &[&AllStats],
+        strategy: ConsolidationStrategy,
+        consolidated: &mut AllStats,
+    ) {
+        consolidated.network =
+            consolidate_optional_field(stats_list, strategy, |x| x.network.as_ref());
+    }
+
+    fn build_charts(
+        &self,
+        entries: &[Option<&AllStats>],
+        most_recent_stats: &AllStats,
+        _dark_mode: bool,
+    ) -> Vec<ChartContext> {
+        let network = match &most_recent_stats.network {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+        let mut charts = build_network_throughput_charts(entries, network);
+        charts.extend(build_network_charts(entries));
+        if network
+            .sockets
+            .as_ref()
+            .and_then(combined_tcp_states)
+            .is_some()
+        {
+            charts.push(build_tcp_states_chart(entries));
+        }
+
+        charts
+    }
+
+    fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        most_recent_stats
+            .network
+            .iter()
+            .filter_map(build_network_section)
+            .collect()
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn name(&self) -> &'static str {
+        "processes"
+    }
+
+    fn collect(&self, _sys: &System, stats: &mut AllStats, kind: CollectionKind) {
+        stats.processes = self.collect_processes(kind).map(Arc::new);
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.processes = collected.processes.clone();
+    }
+
+    fn consolidate(
+        &self,
+        _stats_list: &[&AllStats],
+        _strategy: ConsolidationStrategy,
+        _consolidated: &mut AllStats,
+    ) {
+        // process stats aren't kept in the history, so there's nothing to consolidate
+    }
+
+    fn build_sections(&self, most_recent_stats: &AllStats) -> Vec<DashboardSectionContext> {
+        most_recent_stats
+            .processes
+            .as_deref()
+            .map(|x| build_top_processes_section(x))
+            .into_iter()
+            .collect()
+    }
+}
+
+impl Collector for CgroupCollector {
+    fn name(&self) -> &'static str {
+        "cgroups"
+    }
+
+    fn collect(&self, _sys: &System, stats: &mut AllStats, kind: CollectionKind) {
+        stats.cgroups = self.collect_cgroups(kind).map(Arc::new);
+    }
+
+    fn merge(&self, collected: &AllStats, stats: &mut AllStats) {
+        stats.cgroups = collected.cgroups.clone();
+    }
+
+    fn consolidate(
+        &self,
+        _stats_list: &[&AllStats],
+        _strategy: ConsolidationStrategy,
+        _consolidated: &mut AllStats,
+    ) {
+        // cgroup stats aren't kept in the history, so there's nothing to consolidate
+    }
+
+    fn build_tables(&self, most_recent_stats: &AllStats) -> Vec<TableContext> {
+        most_recent_stats
+            .cgroups
+            .as_deref()
+            .map(|x| build_cgroups_table(x))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Gets the number of seconds between the provided times.
+fn seconds_between(earlier: DateTime<Local>, later: DateTime<Local>) -> f32 {
+    (later - earlier).num_milliseconds() as f32 / 1000.0
+}
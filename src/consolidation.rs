@@ -0,0 +1,421 @@
+//! Strategies for consolidating many stats collections into one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::sensors::{FanStats, SensorStats, TemperatureSensorStats};
+use crate::stats::*;
+
+/// The percentile used by `ConsolidationStrategy::P95`.
+const P95_PERCENTILE: f64 = 0.95;
+
+/// A way to consolidate a list of values into a single value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConsolidationStrategy {
+    /// The mean of the values.
+    #[default]
+    #[serde(rename = "avg")]
+    Average,
+    /// The lowest value.
+    #[serde(rename = "min")]
+    Min,
+    /// The highest value.
+    #[serde(rename = "max")]
+    Max,
+    /// The 95th percentile of the values.
+    #[serde(rename = "p95")]
+    P95,
+    /// The most recent value.
+    #[serde(rename = "last")]
+    Last,
+}
+
+/// Configuration for consolidating stats.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConsolidationConfig {
+    /// The consolidated values to display on the dashboard charts.
+    #[serde(default)]
+    pub chart: ConsolidationStrategy,
+    /// Whether to include the 95th percentile of each stat in consolidated entries.
+    #[serde(default)]
+    pub p95: bool,
+}
+
+impl ConsolidationStrategy {
+    /// Consolidates the provided values, from oldest to newest. Returns `None` if there aren't any.
+    pub fn apply(self, values: &[f64]) -> Option<f64> {
+        let last = *values.last()?;
+        Some(match self {
+            ConsolidationStrategy::Average => values.iter().sum::<f64>() / values.len() as f64,
+            ConsolidationStrategy::Min => values.iter().copied().fold(last, f64::min),
+            ConsolidationStrategy::Max => values.iter().copied().fold(last, f64::max),
+            ConsolidationStrategy::P95 => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                // nearest-rank percentile, so the result is always one of the values
+                let rank = (P95_PERCENTILE * sorted.len() as f64).ceil() as usize;
+                sorted[rank.max(1) - 1]
+            }
+            ConsolidationStrategy::Last => last,
+        })
+    }
+}
+
+/// Clears the stats that are the same however stats are consolidated, like network addresses and listening ports, so the other ways stats are consolidated don't duplicate the averaged stats. Names of things are kept, since they're needed to match up the stats for each of them.
+pub fn clear_unconsolidated(stats: &mut AllStats) {
+    stats.collectors.clear();
+    if let Some(mounts) = &mut stats.filesystems {
+        for mount in mounts {
+            mount.fs_type.clear();
+            mount.mounted_from.clear();
+        }
+    }
+    if let Some(interfaces) = stats.network.as_mut().and_then(|x| x.interfaces.as_mut()) {
+        for interface in interfaces {
+            interface.addresses.clear();
+            interface.operstate = None;
+            interface.mtu = None;
+        }
+    }
+    if let Some(sockets) = stats.network.as_mut().and_then(|x| x.sockets.as_mut()) {
+        sockets.listening_ports = None;
+    }
+}
+
+/// Stats that can be consolidated.
+pub trait Consolidate: Sized {
+    /// Consolidates the provided stats, from oldest to newest, with the provided strategy. Returns `None` if there aren't any.
+    fn consolidate(values: &[&Self], strategy: ConsolidationStrategy) -> Option<Self>;
+}
+
+/// Consolidates a field across the provided stats.
+pub fn consolidate_field<T, F: Consolidate>(
+    values: &[&T],
+    strategy: ConsolidationStrategy,
+    get_field: impl Fn(&T) -> &F,
+) -> Option<F> {
+    let fields: Vec<&F> = values.iter().map(|x| get_field(x)).collect();
+    F::consolidate(&fields, strategy)
+}
+
+/// Consolidates an optional field across the provided stats, ignoring stats that don't have it. Returns `None` if none of them have it.
+pub fn consolidate_optional_field<T, F: Consolidate>(
+    values: &[&T],
+    strategy: ConsolidationStrategy,
+    get_field: impl Fn(&T) -> Option<&F>,
+) -> Option<F> {
+    let fields: Vec<&F> = values.iter().filter_map(|x| get_field(x)).collect();
+    F::consolidate(&fields, strategy)
+}
+
+/// Consolidates lists of stats whose items are matched up by a key, like the stats for each disk. The items in the most recent list are kept, with each of them consolidated with the items that have the same key in the other lists.
+pub fn consolidate_list<T: Consolidate + Clone, K: PartialEq>(
+    lists: &[&Vec<T>],
+    strategy: ConsolidationStrategy,
+    key: impl Fn(&T) -> K,
+) -> Option<Vec<T>> {
+    let last_list = lists.last()?;
+    Some(
+        last_list
+            .iter()
+            .map(|item| {
+                let item_key = key(item);
+                let matching_items: Vec<&T> = lists
+                    .iter()
+                    .filter_map(|list| list.iter().find(|x| key(x) == item_key))
+                    .collect();
+                T::consolidate(&matching_items, strategy).unwrap_or_else(|| item.clone())
+            })
+            .collect(),
+    )
+}
+
+impl Consolidate for f32 {
+    fn consolidate(values: &[&f32], strategy: ConsolidationStrategy) -> Option<f32> {
+        let values: Vec<f64> = values.iter().map(|x| **x as f64).collect();
+        strategy.apply(&values).map(|x| x as f32)
+    }
+}
+
+impl Consolidate for u64 {
+    fn consolidate(values: &[&u64], strategy: ConsolidationStrategy) -> Option<u64> {
+        let values: Vec<f64> = values.iter().map(|x| **x as f64).collect();
+        strategy.apply(&values).map(|x| x.round() as u64)
+    }
+}
+
+impl Consolidate for usize {
+    fn consolidate(values: &[&usize], strategy: ConsolidationStrategy) -> Option<usize> {
+        let values: Vec<f64> = values.iter().map(|x| **x as f64).collect();
+        strategy.apply(&values).map(|x| x.round() as usize)
+    }
+}
+
+impl<T: Consolidate> Consolidate for Vec<T> {
+    /// Consolidates each position in the lists separately, so lists of different lengths can be consolidated.
+    fn consolidate(values: &[&Vec<T>], strategy: ConsolidationStrategy) -> Option<Vec<T>> {
+        let len = values.iter().map(|x| x.len()).max()?;
+        (0..len)
+            .map(|i| consolidate_optional_field(values, strategy, |x| x.get(i)))
+            .collect()
+    }
+}
+
+impl Consolidate for GeneralStats {
+    fn consolidate(values: &[&GeneralStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let last = values.last()?;
+        Some(GeneralStats {
+            uptime_seconds: last.uptime_seconds,
+            boot_timestamp: last.boot_timestamp,
+            load_averages: consolidate_optional_field(values, strategy, |x| {
+                x.load_averages.as_ref()
+            }),
+            pressure: consolidate_optional_field(values, strategy, |x| x.pressure.as_ref()),
+        })
+    }
+}
+
+impl Consolidate for LoadAverages {
+    fn consolidate(values: &[&LoadAverages], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(LoadAverages {
+            one_minute: consolidate_field(values, strategy, |x| &x.one_minute)?,
+            five_minutes: consolidate_field(values, strategy, |x| &x.five_minutes)?,
+            fifteen_minutes: consolidate_field(values, strategy, |x| &x.fifteen_minutes)?,
+        })
+    }
+}
+
+impl Consolidate for PressureStats {
+    fn consolidate(values: &[&PressureStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(PressureStats {
+            cpu: consolidate_field(values, strategy, |x| &x.cpu)?,
+            memory: consolidate_field(values, strategy, |x| &x.memory)?,
+            io: consolidate_field(values, strategy, |x| &x.io)?,
+        })
+    }
+}
+
+impl Consolidate for ResourcePressure {
+    fn consolidate(values: &[&ResourcePressure], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(ResourcePressure {
+            some: consolidate_field(values, strategy, |x| &x.some)?,
+            full: consolidate_optional_field(values, strategy, |x| x.full.as_ref()),
+        })
+    }
+}
+
+impl Consolidate for PressureValues {
+    fn consolidate(values: &[&PressureValues], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(PressureValues {
+            avg10: consolidate_field(values, strategy, |x| &x.avg10)?,
+            avg60: consolidate_field(values, strategy, |x| &x.avg60)?,
+            avg300: consolidate_field(values, strategy, |x| &x.avg300)?,
+            // the total is cumulative, so the most recent one covers all the others
+            total_microseconds: values.last()?.total_microseconds,
+        })
+    }
+}
+
+impl Consolidate for CpuStats {
+    fn consolidate(values: &[&CpuStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        values.last()?;
+        // CPU load isn't available for the first stats collected, so only stats that have it are included
+        Some(CpuStats {
+            per_logical_cpu_load_percent: consolidate_optional_field(values, strategy, |x| {
+                x.per_logical_cpu_load_percent.as_ref()
+            }),
+            aggregate_load_percent: consolidate_optional_field(values, strategy, |x| {
+                x.aggregate_load_percent.as_ref()
+            }),
+            temp_celsius: consolidate_optional_field(values, strategy, |x| x.temp_celsius.as_ref()),
+            per_logical_cpu_breakdown: consolidate_optional_field(values, strategy, |x| {
+                x.per_logical_cpu_breakdown.as_ref()
+            }),
+            aggregate_breakdown: consolidate_optional_field(values, strategy, |x| {
+                x.aggregate_breakdown.as_ref()
+            }),
+        })
+    }
+}
+
+impl Consolidate for CpuBreakdown {
+    fn consolidate(values: &[&CpuBreakdown], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(CpuBreakdown {
+            user: consolidate_field(values, strategy, |x| &x.user)?,
+            nice: consolidate_field(values, strategy, |x| &x.nice)?,
+            system: consolidate_field(values, strategy, |x| &x.system)?,
+            interrupt: consolidate_field(values, strategy, |x| &x.interrupt)?,
+            idle: consolidate_field(values, strategy, |x| &x.idle)?,
+            iowait: consolidate_field(values, strategy, |x| &x.iowait)?,
+            steal: consolidate_field(values, strategy, |x| &x.steal)?,
+        })
+    }
+}
+
+impl Consolidate for MemoryStats {
+    fn consolidate(values: &[&MemoryStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(MemoryStats {
+            used_mb: consolidate_field(values, strategy, |x| &x.used_mb)?,
+            // totals are capacities rather than usage, so the largest one is used
+            total_mb: values.iter().map(|x| x.total_mb).max()?,
+            available_mb: consolidate_field(values, strategy, |x| &x.available_mb)?,
+            buffers_mb: consolidate_field(values, strategy, |x| &x.buffers_mb)?,
+            cached_mb: consolidate_field(values, strategy, |x| &x.cached_mb)?,
+            shared_mb: consolidate_field(values, strategy, |x| &x.shared_mb)?,
+            swap_total_mb: values.iter().map(|x| x.swap_total_mb).max()?,
+            swap_used_mb: consolidate_field(values, strategy, |x| &x.swap_used_mb)?,
+            huge_pages_total: values.iter().map(|x| x.huge_pages_total).max()?,
+            huge_pages_free: consolidate_field(values, strategy, |x| &x.huge_pages_free)?,
+            // the huge page size only changes at boot
+            huge_page_size_kb: values.last()?.huge_page_size_kb,
+        })
+    }
+}
+
+impl Consolidate for MountStats {
+    fn consolidate(values: &[&MountStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let last = values.last()?;
+        Some(MountStats {
+            used_mb: consolidate_field(values, strategy, |x| &x.used_mb)?,
+            inodes_used: consolidate_field(values, strategy, |x| &x.inodes_used)?,
+            inodes_free: consolidate_field(values, strategy, |x| &x.inodes_free)?,
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for DiskStats {
+    fn consolidate(values: &[&DiskStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let last = values.last()?;
+        // the other stats are cumulative, so the most recent ones cover all the others
+        Some(DiskStats {
+            read_bytes_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.read_bytes_per_second.as_ref()
+            }),
+            written_bytes_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.written_bytes_per_second.as_ref()
+            }),
+            reads_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.reads_per_second.as_ref()
+            }),
+            writes_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.writes_per_second.as_ref()
+            }),
+            busy_percent: consolidate_optional_field(values, strategy, |x| x.busy_percent.as_ref()),
+            average_queue_depth: consolidate_optional_field(values, strategy, |x| {
+                x.average_queue_depth.as_ref()
+            }),
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for SensorStats {
+    fn consolidate(values: &[&SensorStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let temperatures: Vec<&Vec<TemperatureSensorStats>> =
+            values.iter().map(|x| &x.temperatures).collect();
+        let fans: Vec<&Vec<FanStats>> = values.iter().map(|x| &x.fans).collect();
+        Some(SensorStats {
+            temperatures: consolidate_list(&temperatures, strategy, |x| {
+                (x.chip.clone(), x.label.clone())
+            })?,
+            fans: consolidate_list(&fans, strategy, |x| (x.chip.clone(), x.label.clone()))?,
+        })
+    }
+}
+
+impl Consolidate for TemperatureSensorStats {
+    fn consolidate(
+        values: &[&TemperatureSensorStats],
+        strategy: ConsolidationStrategy,
+    ) -> Option<Self> {
+        let last = values.last()?;
+        Some(TemperatureSensorStats {
+            temp_celsius: consolidate_field(values, strategy, |x| &x.temp_celsius)?,
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for FanStats {
+    fn consolidate(values: &[&FanStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let last = values.last()?;
+        Some(FanStats {
+            rpm: consolidate_field(values, strategy, |x| &x.rpm)?,
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for NetworkStats {
+    fn consolidate(values: &[&NetworkStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        values.last()?;
+        let interfaces: Vec<&Vec<NetworkInterfaceStats>> = values
+            .iter()
+            .filter_map(|x| x.interfaces.as_ref())
+            .collect();
+        Some(NetworkStats {
+            interfaces: consolidate_list(&interfaces, strategy, |x| x.name.clone()),
+            sockets: consolidate_optional_field(values, strategy, |x| x.sockets.as_ref()),
+        })
+    }
+}
+
+impl Consolidate for NetworkInterfaceStats {
+    fn consolidate(
+        values: &[&NetworkInterfaceStats],
+        strategy: ConsolidationStrategy,
+    ) -> Option<Self> {
+        let last = values.last()?;
+        // the other stats are cumulative or rarely change, so the most recent ones are used
+        Some(NetworkInterfaceStats {
+            sent_bytes_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.sent_bytes_per_second.as_ref()
+            }),
+            received_bytes_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.received_bytes_per_second.as_ref()
+            }),
+            sent_packets_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.sent_packets_per_second.as_ref()
+            }),
+            received_packets_per_second: consolidate_optional_field(values, strategy, |x| {
+                x.received_packets_per_second.as_ref()
+            }),
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for SocketStats {
+    fn consolidate(values: &[&SocketStats], strategy: ConsolidationStrategy) -> Option<Self> {
+        let last = values.last()?;
+        // listening ports are a snapshot, so the most recent ones are used
+        Some(SocketStats {
+            tcp_in_use: consolidate_field(values, strategy, |x| &x.tcp_in_use)?,
+            tcp_orphaned: consolidate_field(values, strategy, |x| &x.tcp_orphaned)?,
+            udp_in_use: consolidate_field(values, strategy, |x| &x.udp_in_use)?,
+            tcp6_in_use: consolidate_field(values, strategy, |x| &x.tcp6_in_use)?,
+            udp6_in_use: consolidate_field(values, strategy, |x| &x.udp6_in_use)?,
+            tcp_states: consolidate_optional_field(values, strategy, |x| x.tcp_states.as_ref()),
+            tcp6_states: consolidate_optional_field(values, strategy, |x| x.tcp6_states.as_ref()),
+            ..(*last).clone()
+        })
+    }
+}
+
+impl Consolidate for TcpStateCounts {
+    fn consolidate(values: &[&TcpStateCounts], strategy: ConsolidationStrategy) -> Option<Self> {
+        Some(TcpStateCounts {
+            established: consolidate_field(values, strategy, |x| &x.established)?,
+            syn_sent: consolidate_field(values, strategy, |x| &x.syn_sent)?,
+            syn_recv: consolidate_field(values, strategy, |x| &x.syn_recv)?,
+            fin_wait1: consolidate_field(values, strategy, |x| &x.fin_wait1)?,
+            fin_wait2: consolidate_field(values, strategy, |x| &x.fin_wait2)?,
+            time_wait: consolidate_field(values, strategy, |x| &x.time_wait)?,
+            close: consolidate_field(values, strategy, |x| &x.close)?,
+            close_wait: consolidate_field(values, strategy, |x| &x.close_wait)?,
+            last_ack: consolidate_field(values, strategy, |x| &x.last_ack)?,
+            listen: consolidate_field(values, strategy, |x| &x.listen)?,
+            closing: consolidate_field(values, strategy, |x| &x.closing)?,
+        })
+    }
+}
@@ -1,10 +1,21 @@
 //! Context for the dashboard template.
 
-use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat, Utc};
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, SecondsFormat, Utc};
 use serde::Serialize;
 
 use crate::{
-    stats::{GeneralStats, MountStats, NetworkStats},
+    alerts::{AlertState, AlertStatus},
+    cgroups::{CgroupSortOrder, CgroupStats},
+    collectors::CollectorRegistry,
+    consolidation::ConsolidationStrategy,
+    processes::{ProcessSortOrder, ProcessStats},
+    sensors::SensorStats,
+    stats::{
+        AllStats, DiskStats, GeneralStats, MountStats, NetworkStats, SocketStats, TcpStateCounts,
+        BYTES_PER_MB,
+    },
     stats_history::StatsHistory,
 };
 
@@ -13,17 +24,48 @@ const CPU_PER_LOGICAL_CPU_LINE_COLOR_DARK_MODE: &str = "#ffffff44"; // gray
 const CPU_AGGREGATE_LINE_COLOR: &str = "#ffcc00"; // yellow
 const CPU_AGGREGATE_FILL_COLOR: &str = "#ffcc0099"; // yellow
 
+const CPU_USER_LINE_COLOR: &str = "#3399ff"; // blue
+const CPU_USER_FILL_COLOR: &str = "#3399ff99"; // blue
+const CPU_NICE_LINE_COLOR: &str = "#33cc99"; // sea green
+const CPU_NICE_FILL_COLOR: &str = "#33cc9999"; // sea green
+const CPU_SYSTEM_LINE_COLOR: &str = "#ff3333"; // red
+const CPU_SYSTEM_FILL_COLOR: &str = "#ff333399"; // red
+const CPU_INTERRUPT_LINE_COLOR: &str = "#ff9933"; // orange
+const CPU_INTERRUPT_FILL_COLOR: &str = "#ff993399"; // orange
+const CPU_IOWAIT_LINE_COLOR: &str = "#9966cc"; // purple
+const CPU_IOWAIT_FILL_COLOR: &str = "#9966cc99"; // purple
+const CPU_STEAL_LINE_COLOR: &str = "#666666"; // dark gray
+const CPU_STEAL_FILL_COLOR: &str = "#66666699"; // dark gray
+
 const TEMPERATURE_LINE_COLOR: &str = "#990000"; // red
 const TEMPERATURE_FILL_COLOR: &str = "#99000099"; // red
+const SENSOR_LINE_COLORS: [&str; 6] = [
+    "#990000", // red
+    "#ff6600", // orange
+    "#cc9900", // gold
+    "#cc0066", // raspberry
+    "#663300", // brown
+    "#ff3399", // pink
+];
+const DEFAULT_MAX_TEMPERATURE: f32 = 85.0;
 
 const MEM_LINE_COLOR: &str = "#0055ff"; // blue
 const MEM_FILL_COLOR: &str = "#0055ff99"; // blue
+const MEM_CACHE_LINE_COLOR: &str = "#66aaff"; // light blue
+const MEM_CACHE_FILL_COLOR: &str = "#66aaff99"; // light blue
+const SWAP_LINE_COLOR: &str = "#cc3399"; // magenta
+const SWAP_FILL_COLOR: &str = "#cc339999"; // magenta
 
 const SENT_LINE_COLOR: &str = "#44eeaa"; // blue-green
 const SENT_FILL_COLOR: &str = "#44eeaa99"; // blue-green
 const RECEIVED_LINE_COLOR: &str = "#44dd22"; // green
 const RECEIVED_FILL_COLOR: &str = "#44dd2299"; // green
 
+const DISK_READ_LINE_COLOR: &str = "#aa66ff"; // lavender
+const DISK_READ_FILL_COLOR: &str = "#aa66ff99"; // lavender
+const DISK_WRITE_LINE_COLOR: &str = "#ff66aa"; // light pink
+const DISK_WRITE_FILL_COLOR: &str = "#ff66aa99"; // light pink
+
 const SEND_ERRORS_LINE_COLOR: &str = "#ff8800"; // yellow-orange
 const SEND_ERRORS_FILL_COLOR: &str = "#ff880099"; // yellow-orange
 const RECEIVE_ERRORS_LINE_COLOR: &str = "#ff4400"; // orange
@@ -34,6 +76,13 @@ const TCP_FILL_COLOR: &str = "#44eedd99"; // teal
 const UDP_LINE_COLOR: &str = "#44bbdd"; // light blue
 const UDP_FILL_COLOR: &str = "#44bbdd99"; // light blue
 
+const TCP_ESTABLISHED_LINE_COLOR: &str = "#44dd22"; // green
+const TCP_ESTABLISHED_FILL_COLOR: &str = "#44dd2299"; // green
+const TCP_TIME_WAIT_LINE_COLOR: &str = "#44bbdd"; // light blue
+const TCP_TIME_WAIT_FILL_COLOR: &str = "#44bbdd99"; // light blue
+const TCP_CLOSE_WAIT_LINE_COLOR: &str = "#ff4400"; // orange
+const TCP_CLOSE_WAIT_FILL_COLOR: &str = "#ff440099"; // orange
+
 const LOAD_AVERAGE_1_LINE_COLOR: &str = "#ff00ff"; // pink
 const LOAD_AVERAGE_1_FILL_COLOR: &str = "#ff00ff99"; // pink
 const LOAD_AVERAGE_5_LINE_COLOR: &str = "#bb00ff"; // purple
@@ -41,19 +90,52 @@ const LOAD_AVERAGE_5_FILL_COLOR: &str = "#bb00ff99"; // purple
 const LOAD_AVERAGE_15_LINE_COLOR: &str = "#7700ff"; // dark purple
 const LOAD_AVERAGE_15_FILL_COLOR: &str = "#7700ff99"; // dark purple
 
+const CPU_PRESSURE_LINE_COLOR: &str = "#ffcc00"; // yellow
+const CPU_PRESSURE_FILL_COLOR: &str = "#ffcc0099"; // yellow
+const MEMORY_PRESSURE_LINE_COLOR: &str = "#0055ff"; // blue
+const MEMORY_PRESSURE_FILL_COLOR: &str = "#0055ff99"; // blue
+const IO_PRESSURE_LINE_COLOR: &str = "#aa66ff"; // lavender
+const IO_PRESSURE_FILL_COLOR: &str = "#aa66ff99"; // lavender
+
+const NUM_TOP_PROCESSES: usize = 10;
+
+const NETWORK_THROUGHPUT_CHART_ID_PREFIX: &str = "network-throughput-chart-";
+const DISK_IO_CHART_ID_PREFIX: &str = "disk-io-chart-";
+const BYTES_PER_KB: f32 = 1000.0;
+
+/// Configuration for building dashboards.
+#[derive(Clone, Copy)]
+pub struct DashboardConfig {
+    /// The longest amount of time that can pass between consecutive entries in a history before a gap is shown in the charts.
+    pub max_gap: Duration,
+    /// Which of the consolidated values of each stat to display in the charts.
+    pub chart_consolidation: ConsolidationStrategy,
+}
+
 /// Context for the dashboard template.
 #[derive(Serialize)]
 pub struct DashboardContext {
     title: String,
     dark_mode: bool,
+    /// Whether the dashboard should update itself as new stats are collected.
+    live_updates: bool,
+    /// The maximum number of entries to display in the charts.
+    max_entries: usize,
+    /// Messages describing the alerts that are currently firing.
+    firing_alerts: Vec<String>,
+    /// The time each entry in the charts was collected, in milliseconds since the Unix epoch. Gaps are `None`.
+    entry_times: Vec<Option<i64>>,
+    /// The longest amount of time that can pass between consecutive entries before a gap is shown in the charts, in milliseconds.
+    max_gap_millis: i64,
     charts: Vec<ChartContext>,
+    tables: Vec<TableContext>,
     sections: Vec<DashboardSectionContext>,
     last_update_time: String,
 }
 
 /// Context for a single chart in a dashboard.
 #[derive(Serialize)]
-struct ChartContext {
+pub struct ChartContext {
     /// The id of this chart. Must be unique.
     id: String,
     /// The title of this chart.
@@ -70,12 +152,81 @@ struct ChartContext {
     min_y: f32,
     /// The highest possible Y value expected for this chart.
     max_y: f32,
+    /// Whether the datasets in this chart are stacked on top of each other.
+    stacked: bool,
+    /// First line of text to diplay beside the chart.
+    accompanying_text_1: String,
+    /// Second line of text to diplay beside the chart.
+    accompanying_text_2: String,
+}
+
+/// The values to add to the charts in a live dashboard when new stats are collected.
+#[derive(Serialize)]
+pub struct DashboardChartsUpdate {
+    /// Whether the values are for a new entry in the charts, rather than replacing the most recent entry.
+    new_entry: bool,
+    /// The X axis label for the new values.
+    x_value: String,
+    /// The time the stats were collected, in milliseconds since the Unix epoch.
+    collection_time_millis: i64,
+    /// The time the stats were collected.
+    last_update_time: String,
+    /// The update for each chart.
+    charts: Vec<ChartUpdate>,
+}
+
+/// The values to add to a single chart in a live dashboard.
+#[derive(Serialize)]
+struct ChartUpdate {
+    /// The id of the chart to update.
+    id: String,
+    /// The new value for each dataset in the chart, by dataset name.
+    values: BTreeMap<String, Option<f32>>,
     /// First line of text to diplay beside the chart.
     accompanying_text_1: String,
     /// Second line of text to diplay beside the chart.
     accompanying_text_2: String,
 }
 
+impl DashboardChartsUpdate {
+    /// Builds the chart updates for newly collected stats. The charts are built the same way as when the dashboard is rendered, so they show the same values.
+    ///
+    /// # Arguments
+    /// * `stats` - The newly collected stats.
+    /// * `new_entry` - Whether the stats were added to the history as a new entry, rather than replacing the most recent entry.
+    /// * `collectors` - The collectors to build charts for.
+    pub fn from_stats(
+        stats: &AllStats,
+        new_entry: bool,
+        collectors: &CollectorRegistry,
+    ) -> DashboardChartsUpdate {
+        let charts = collectors
+            .build_charts(&[Some(stats)], stats, false)
+            .into_iter()
+            .map(|chart| ChartUpdate {
+                id: chart.id,
+                values: chart
+                    .datasets
+                    .into_iter()
+                    .map(|x| (x.name, x.values.last().copied().flatten()))
+                    .collect(),
+                accompanying_text_1: chart.accompanying_text_1,
+                accompanying_text_2: chart.accompanying_text_2,
+            })
+            .collect();
+
+        DashboardChartsUpdate {
+            new_entry,
+            x_value: format_time(stats.collection_time),
+            collection_time_millis: stats.collection_time.timestamp_millis(),
+            last_update_time: stats
+                .collection_time
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            charts,
+        }
+    }
+}
+
 /// Context for a single dataset in a chart.
 #[derive(Serialize)]
 struct DatasetContext {
@@ -85,15 +236,37 @@ struct DatasetContext {
     line_color_code: String,
     /// Color code used for the area under the line. Only relevant if `fill` is `true`.
     fill_color_code: String,
-    /// The values in this dataset.
-    values: Vec<f32>,
+    /// The values in this dataset. `None` values are displayed as gaps.
+    values: Vec<Option<f32>>,
     /// Whether to fill the area under the line.
     fill: bool,
 }
 
+/// Context for a sortable table in a dashboard.
+#[derive(Serialize)]
+pub struct TableContext {
+    /// The id of this table. Must be unique.
+    id: String,
+    /// The title of this table.
+    title: String,
+    /// The names of the columns in this table.
+    columns: Vec<String>,
+    /// The rows in this table, each with a cell for every column.
+    rows: Vec<Vec<TableCellContext>>,
+}
+
+/// Context for a single cell in a table.
+#[derive(Serialize)]
+struct TableCellContext {
+    /// The text to display in this cell.
+    text: String,
+    /// The value to sort this cell's column by. Numeric values are sorted numerically, and everything else is sorted alphabetically.
+    sort_value: String,
+}
+
 /// Context for a section of a dashboard.
 #[derive(Serialize)]
-struct DashboardSectionContext {
+pub struct DashboardSectionContext {
     /// The name of the section.
     name: String,
     /// The stats in the section.
@@ -109,6 +282,8 @@ struct DashboardSubsectionContext {
     name: String,
     /// The stats in the subsection.
     stats: Vec<String>,
+    /// Whether the subsection needs attention and should be highlighted.
+    highlighted: bool,
 }
 
 impl DashboardContext {
@@ -116,18 +291,41 @@ impl DashboardContext {
     ///
     /// # Arguments
     /// * `stats_history` - The stats history to use to populate the context.
+    /// * `collectors` - The collectors to build charts and sections for.
+    /// * `config` - Configuration for building the dashboard.
+    /// * `alert_statuses` - The current status of each alert.
+    /// * `live_updates` - Whether the dashboard should update itself as new stats are collected.
     /// * `dark_mode` - Whether dark mode is enabled or not.
-    pub fn from_history(stats_history: &StatsHistory, dark_mode: bool) -> DashboardContext {
+    pub fn from_history(
+        stats_history: &StatsHistory,
+        collectors: &CollectorRegistry,
+        config: &DashboardConfig,
+        alert_statuses: &[AlertStatus],
+        live_updates: bool,
+        dark_mode: bool,
+    ) -> DashboardContext {
         let title = "Dashboard".to_string();
+        let max_entries = stats_history.max_size();
+        let max_gap_millis = config.max_gap.num_milliseconds();
+        let firing_alerts = alert_statuses
+            .iter()
+            .filter(|status| status.state == AlertState::Firing)
+            .map(|status| status.message.clone())
+            .collect();
 
-        let mut sections = Vec::new();
         let most_recent_stats = match stats_history.get_most_recent_stats() {
             Some(x) => x,
             None => {
                 return DashboardContext {
                     title,
                     dark_mode,
+                    live_updates,
+                    max_entries,
+                    firing_alerts,
+                    entry_times: Vec::new(),
+                    max_gap_millis,
                     charts: Vec::new(),
+                    tables: Vec::new(),
                     sections: vec![DashboardSectionContext {
                         name: "No stats yet".to_string(),
                         stats: Vec::new(),
@@ -138,26 +336,26 @@ impl DashboardContext {
             }
         };
 
-        if let Some(x) = build_general_section(&most_recent_stats.general) {
-            sections.push(x);
-        }
-        if let Some(x) = build_network_section(&most_recent_stats.network) {
-            sections.push(x);
-        }
-        if let Some(x) = &most_recent_stats.filesystems {
-            sections.push(build_filesystems_section(x));
-        }
+        let sections = collectors.build_sections(most_recent_stats);
+        let tables = collectors.build_tables(most_recent_stats);
 
-        let mut charts = Vec::new();
-        charts.extend(build_cpu_charts(stats_history, dark_mode));
-        charts.push(build_memory_chart(stats_history));
-        charts.push(build_load_average_chart(stats_history));
-        charts.extend(build_network_charts(stats_history));
+        let entries = entries_with_gaps(stats_history, config);
+        let charts = collectors.build_charts(&entries, most_recent_stats, dark_mode);
+        let entry_times = entries
+            .iter()
+            .map(|x| x.map(|stats| stats.collection_time.timestamp_millis()))
+            .collect();
 
         DashboardContext {
             title,
             dark_mode,
+            live_updates,
+            max_entries,
+            firing_alerts,
+            entry_times,
+            max_gap_millis,
             charts,
+            tables,
             sections,
             last_update_time: most_recent_stats
                 .collection_time
@@ -166,7 +364,7 @@ impl DashboardContext {
     }
 }
 
-fn build_general_section(stats: &GeneralStats) -> Option<DashboardSectionContext> {
+pub fn build_general_section(stats: &GeneralStats) -> Option<DashboardSectionContext> {
     let mut stat_strings = Vec::new();
     if let Some(x) = stats.uptime_seconds {
         stat_strings.push(format!("Uptime: {} seconds", x))
@@ -190,10 +388,10 @@ fn build_general_section(stats: &GeneralStats) -> Option<DashboardSectionContext
     }
 }
 
-fn build_network_section(network_stats: &NetworkStats) -> Option<DashboardSectionContext> {
+pub fn build_network_section(network_stats: &NetworkStats) -> Option<DashboardSectionContext> {
     let mut subsections = Vec::new();
-    match &network_stats.sockets {
-        Some(socket_stats) => subsections.push(DashboardSubsectionContext {
+    if let Some(socket_stats) = &network_stats.sockets {
+        subsections.push(DashboardSubsectionContext {
             name: "Sockets".to_string(),
             stats: vec![
                 format!(
@@ -205,32 +403,79 @@ fn build_network_section(network_stats: &NetworkStats) -> Option<DashboardSectio
                     socket_stats.udp_in_use, socket_stats.udp6_in_use
                 ),
             ],
-        }),
-        None => (),
-    }
-
-    match &network_stats.interfaces {
-        Some(x) => {
-            for interface in x {
-                subsections.push(DashboardSubsectionContext {
-                    name: interface.name.clone(),
-                    stats: vec![
-                        format!("IP addresses: {}", interface.addresses.join(", ")),
-                        format!(
-                            "Sent: {} packets, {} MB, {} errors",
-                            interface.sent_packets, interface.sent_mb, interface.send_errors
-                        ),
-                        format!(
-                            "Received: {} packets, {} MB, {} errors",
-                            interface.received_packets,
-                            interface.received_mb,
-                            interface.receive_errors
-                        ),
-                    ],
-                })
+            highlighted: false,
+        });
+        if let Some(states) = combined_tcp_states(socket_stats) {
+            subsections.push(DashboardSubsectionContext {
+                name: "TCP Connections".to_string(),
+                stats: vec![
+                    format!(
+                        "Open: {} established, {} SYN sent, {} SYN received",
+                        states.established, states.syn_sent, states.syn_recv
+                    ),
+                    format!(
+                        "Closing: {} FIN wait 1, {} FIN wait 2, {} time wait, {} close wait, {} last ACK, {} closing",
+                        states.fin_wait1,
+                        states.fin_wait2,
+                        states.time_wait,
+                        states.close_wait,
+                        states.last_ack,
+                        states.closing
+                    ),
+                    format!("Listening: {}", states.listen),
+                ],
+                highlighted: false,
+            });
+        }
+        if let Some(ports) = &socket_stats.listening_ports {
+            subsections.push(DashboardSubsectionContext {
+                name: "Listening Ports".to_string(),
+                stats: ports
+                    .iter()
+                    .map(|x| format!("{} on {} ({})", x.port, x.address, x.protocol))
+                    .collect(),
+                highlighted: false,
+            });
+        }
+    }
+
+    if let Some(x) = &network_stats.interfaces {
+        for interface in x {
+            let mut stats = Vec::new();
+            if let Some(operstate) = &interface.operstate {
+                stats.push(format!("State: {}", operstate));
+            }
+            let mut link = Vec::new();
+            if let Some(speed) = interface.speed_mbps {
+                link.push(format!("{} Mb/s", speed));
+            }
+            if let Some(duplex) = &interface.duplex {
+                link.push(format!("{} duplex", duplex));
             }
+            if let Some(mtu) = interface.mtu {
+                link.push(format!("MTU {}", mtu));
+            }
+            if !link.is_empty() {
+                stats.push(format!("Link: {}", link.join(", ")));
+            }
+            if let Some(mac_address) = &interface.mac_address {
+                stats.push(format!("MAC address: {}", mac_address));
+            }
+            stats.push(format!("IP addresses: {}", interface.addresses.join(", ")));
+            stats.push(format!(
+                "Sent: {} packets, {} MB, {} errors",
+                interface.sent_packets, interface.sent_mb, interface.send_errors
+            ));
+            stats.push(format!(
+                "Received: {} packets, {} MB, {} errors",
+                interface.received_packets, interface.received_mb, interface.receive_errors
+            ));
+            subsections.push(DashboardSubsectionContext {
+                name: interface.name.clone(),
+                stats,
+                highlighted: false,
+            })
         }
-        None => (),
     }
 
     if subsections.is_empty() {
@@ -244,7 +489,7 @@ fn build_network_section(network_stats: &NetworkStats) -> Option<DashboardSectio
     }
 }
 
-fn build_filesystems_section(mount_stats: &[MountStats]) -> DashboardSectionContext {
+pub fn build_filesystems_section(mount_stats: &[MountStats]) -> DashboardSectionContext {
     let mut total_used_mb = 0;
     let mut total_total_mb = 0;
     let mut subsections = Vec::new();
@@ -252,6 +497,13 @@ fn build_filesystems_section(mount_stats: &[MountStats]) -> DashboardSectionCont
         total_used_mb += mount.used_mb;
         total_total_mb += mount.total_mb;
         let used_pct = ((mount.used_mb as f64) / (mount.total_mb as f64)) * 100.0;
+        let inodes = match mount.inodes_used_percent() {
+            Some(inodes_used_pct) => format!(
+                "Inodes used: {} / {} ({:.2}%)",
+                mount.inodes_used, mount.inodes_total, inodes_used_pct
+            ),
+            None => "Inodes used: not reported".to_string(),
+        };
         subsections.push(DashboardSubsectionContext {
             name: mount.mounted_on.clone(),
             stats: vec![
@@ -261,7 +513,9 @@ fn build_filesystems_section(mount_stats: &[MountStats]) -> DashboardSectionCont
                     "Used: {} / {} MB ({:.2}%)",
                     mount.used_mb, mount.total_mb, used_pct
                 ),
+                inodes,
             ],
+            highlighted: mount.inodes_exhausted(),
         });
     }
 
@@ -276,28 +530,163 @@ fn build_filesystems_section(mount_stats: &[MountStats]) -> DashboardSectionCont
     }
 }
 
-fn build_cpu_charts(stats_history: &StatsHistory, dark_mode: bool) -> Vec<ChartContext> {
+pub fn build_sensors_section(sensor_stats: &SensorStats) -> Option<DashboardSectionContext> {
+    let mut subsections = Vec::new();
+    for sensor in &sensor_stats.temperatures {
+        let mut stats = vec![format!("Temperature: {:.2}°C", sensor.temp_celsius)];
+        if let Some(x) = sensor.high_celsius {
+            stats.push(format!("High: {:.2}°C", x));
+        }
+        if let Some(x) = sensor.critical_celsius {
+            stats.push(format!("Critical: {:.2}°C", x));
+        }
+        subsections.push(DashboardSubsectionContext {
+            name: sensor.display_name(),
+            stats,
+            highlighted: false,
+        });
+    }
+    for fan in &sensor_stats.fans {
+        subsections.push(DashboardSubsectionContext {
+            name: fan.display_name(),
+            stats: vec![format!("Speed: {} RPM", fan.rpm)],
+            highlighted: false,
+        });
+    }
+
+    if subsections.is_empty() {
+        None
+    } else {
+        Some(DashboardSectionContext {
+            name: "Sensors".to_string(),
+            stats: Vec::new(),
+            subsections,
+        })
+    }
+}
+
+pub fn build_top_processes_section(processes: &[ProcessStats]) -> DashboardSectionContext {
+    let mut top_processes = processes.to_vec();
+    ProcessSortOrder::Cpu.sort(&mut top_processes);
+
+    let mut subsections = Vec::new();
+    for process in top_processes.iter().take(NUM_TOP_PROCESSES) {
+        subsections.push(DashboardSubsectionContext {
+            name: format!("{} ({})", process.name, process.pid),
+            stats: vec![
+                format!("User: {}", process.user),
+                format!("State: {}", process.state),
+                format!("CPU: {:.2}%", process.cpu_percent),
+                format!("Memory: {} KB", process.resident_memory_kb),
+                format!("Threads: {}", process.threads),
+                format!("Command: {}", process.command_line),
+            ],
+            highlighted: false,
+        });
+    }
+
+    DashboardSectionContext {
+        name: "Top processes".to_string(),
+        stats: vec![format!("Total processes: {}", processes.len())],
+        subsections,
+    }
+}
+
+pub fn build_cgroups_table(cgroups: &[CgroupStats]) -> TableContext {
+    let mut sorted_cgroups = cgroups.to_vec();
+    CgroupSortOrder::Cpu.sort(&mut sorted_cgroups);
+
+    let rows = sorted_cgroups
+        .iter()
+        .map(|cgroup| {
+            vec![
+                TableCellContext {
+                    text: cgroup.name.clone(),
+                    sort_value: cgroup.name.clone(),
+                },
+                TableCellContext {
+                    text: format!("{:.2}%", cgroup.cpu_percent),
+                    sort_value: cgroup.cpu_percent.to_string(),
+                },
+                bytes_cell(cgroup.memory_current_bytes, "N/A", "0"),
+                // cgroups without a memory limit are sorted as if they had the highest one
+                bytes_cell(cgroup.memory_max_bytes, "None", "Infinity"),
+                bytes_cell(cgroup.io_read_bytes, "N/A", "0"),
+                bytes_cell(cgroup.io_written_bytes, "N/A", "0"),
+                TableCellContext {
+                    text: match (cgroup.pids_current, cgroup.pids_max) {
+                        (Some(current), Some(max)) => format!("{} / {}", current, max),
+                        (Some(current), None) => current.to_string(),
+                        (None, _) => "N/A".to_string(),
+                    },
+                    sort_value: cgroup.pids_current.unwrap_or(0).to_string(),
+                },
+            ]
+        })
+        .collect();
+
+    TableContext {
+        id: "cgroups-table".to_string(),
+        title: format!("Cgroups ({})", cgroups.len()),
+        columns: vec![
+            "Cgroup".to_string(),
+            "CPU".to_string(),
+            "Memory".to_string(),
+            "Memory limit".to_string(),
+            "I/O read".to_string(),
+            "I/O written".to_string(),
+            "PIDs".to_string(),
+        ],
+        rows,
+    }
+}
+
+/// Builds a table cell displaying a number of bytes in megabytes, or the provided text and sort value if there's no value.
+fn bytes_cell(
+    bytes: Option<u64>,
+    missing_text: &str,
+    missing_sort_value: &str,
+) -> TableCellContext {
+    match bytes {
+        Some(x) => TableCellContext {
+            text: format!("{:.2} MB", x as f64 / BYTES_PER_MB as f64),
+            sort_value: x.to_string(),
+        },
+        None => TableCellContext {
+            text: missing_text.to_string(),
+            sort_value: missing_sort_value.to_string(),
+        },
+    }
+}
+
+pub fn build_cpu_charts(entries: &[Option<&AllStats>], dark_mode: bool) -> Vec<ChartContext> {
     let mut charts = Vec::new();
     let mut cpu_datasets = Vec::new();
     let mut aggregate_values = Vec::new();
     let mut per_logical_cpu_values = Vec::new();
-    let mut temp_values = Vec::new();
     let mut x_values = Vec::new();
     let empty_vec = Vec::new();
-    for stats in stats_history.into_iter() {
-        aggregate_values.push(stats.cpu.aggregate_load_percent.unwrap_or(0.0));
-        per_logical_cpu_values.push(
-            stats
-                .cpu
-                .per_logical_cpu_load_percent
-                .as_ref()
-                .unwrap_or(&empty_vec),
-        );
-        temp_values.push(stats.cpu.temp_celsius.unwrap_or(0.0));
-        x_values.push(format_time(stats.collection_time));
+    for &entry in entries {
+        match entry {
+            Some(stats) => {
+                let cpu = stats.cpu.as_ref();
+                aggregate_values.push(Some(
+                    cpu.and_then(|x| x.aggregate_load_percent).unwrap_or(0.0),
+                ));
+                per_logical_cpu_values.push(Some(
+                    cpu.and_then(|x| x.per_logical_cpu_load_percent.as_ref())
+                        .unwrap_or(&empty_vec),
+                ));
+            }
+            None => {
+                aggregate_values.push(None);
+                per_logical_cpu_values.push(None);
+            }
+        }
+        x_values.push(format_entry_time(entry));
     }
 
-    let usage_accompanying_text = format!("{:.2}%", aggregate_values.last().unwrap_or(&0.0));
+    let usage_accompanying_text = format!("{:.2}%", last_value(&aggregate_values));
 
     cpu_datasets.push(DatasetContext {
         name: "Aggregate".to_string(),
@@ -308,17 +697,26 @@ fn build_cpu_charts(stats_history: &StatsHistory, dark_mode: bool) -> Vec<ChartC
     });
 
     // TODO there's gotta be a better way to do this
-    let num_logical_cpus = match per_logical_cpu_values.first() {
+    let num_logical_cpus = match per_logical_cpu_values.iter().flatten().next() {
         Some(x) => x.len(),
         None => 0,
     };
-    let mut per_logical_cpu_values_flipped: Vec<Vec<f32>> = Vec::new();
+    let mut per_logical_cpu_values_flipped: Vec<Vec<Option<f32>>> = Vec::new();
     for _ in 0..num_logical_cpus {
         per_logical_cpu_values_flipped.push(Vec::new());
     }
-    for vec in per_logical_cpu_values {
-        for (i, x) in vec.iter().enumerate() {
-            per_logical_cpu_values_flipped[i].push(*x);
+    for values in per_logical_cpu_values {
+        match values {
+            Some(vec) => {
+                for (i, x) in vec.iter().enumerate() {
+                    per_logical_cpu_values_flipped[i].push(Some(*x));
+                }
+            }
+            None => {
+                for flipped_values in per_logical_cpu_values_flipped.iter_mut() {
+                    flipped_values.push(None);
+                }
+            }
         }
     }
 
@@ -346,62 +744,255 @@ fn build_cpu_charts(stats_history: &StatsHistory, dark_mode: bool) -> Vec<ChartC
         x_values: x_values.clone(),
         min_y: 0.0,
         max_y: 100.0,
+        stacked: false,
         accompanying_text_1: usage_accompanying_text,
         accompanying_text_2: "".to_string(),
     });
 
-    let temp_accompanying_text = format!("{:.2}°C", temp_values.last().unwrap_or(&0.0));
-    charts.push(ChartContext {
-        id: "cpu-temp-chart".to_string(),
-        title: "Temperature".to_string(),
-        datasets: vec![DatasetContext {
+    charts
+}
+
+pub fn build_temperature_chart(
+    entries: &[Option<&AllStats>],
+    most_recent_stats: &AllStats,
+) -> ChartContext {
+    let x_values: Vec<String> = entries.iter().map(|&x| format_entry_time(x)).collect();
+    let (sensors, fans) = match &most_recent_stats.sensors {
+        Some(x) => (x.temperatures.as_slice(), x.fans.as_slice()),
+        None => (&[][..], &[][..]),
+    };
+
+    let mut datasets = Vec::new();
+    if sensors.is_empty() {
+        // fall back to the CPU temperature if there aren't any sensors
+        datasets.push(DatasetContext {
             name: "Celsius".to_string(),
             line_color_code: TEMPERATURE_LINE_COLOR.to_string(),
             fill_color_code: TEMPERATURE_FILL_COLOR.to_string(),
-            values: temp_values,
+            values: entries
+                .iter()
+                .map(|entry| {
+                    entry.map(|stats| {
+                        stats
+                            .cpu
+                            .as_ref()
+                            .and_then(|x| x.temp_celsius)
+                            .unwrap_or(0.0)
+                    })
+                })
+                .collect(),
             fill: true,
-        }],
+        });
+    }
+    for (i, sensor) in sensors.iter().enumerate() {
+        let values = entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .and_then(|stats| stats.sensors.as_ref())
+                    .and_then(|x| {
+                        x.temperatures
+                            .iter()
+                            .find(|x| x.chip == sensor.chip && x.label == sensor.label)
+                    })
+                    .map(|x| x.temp_celsius)
+            })
+            .collect();
+        datasets.push(DatasetContext {
+            name: sensor.display_name(),
+            line_color_code: SENSOR_LINE_COLORS[i % SENSOR_LINE_COLORS.len()].to_string(),
+            fill_color_code: "".to_string(),
+            values,
+            fill: false,
+        });
+    }
+
+    // sensors that weren't read in the most recent entry don't count, and there may not be any left
+    let hottest_temp = datasets
+        .iter()
+        .filter_map(|x| x.values.last().copied().flatten())
+        .reduce(f32::max);
+    let max_y = sensors
+        .iter()
+        .filter_map(|x| x.critical_celsius)
+        .reduce(f32::max)
+        .unwrap_or(DEFAULT_MAX_TEMPERATURE);
+    let fan_speeds = fans
+        .iter()
+        .map(|x| format!("{}: {} RPM", x.display_name(), x.rpm))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    ChartContext {
+        id: "cpu-temp-chart".to_string(),
+        title: "Temperature".to_string(),
+        datasets,
         x_label: "Time".to_string(),
         y_label: "Temperature (C)".to_string(),
         x_values,
         min_y: 0.0,
-        max_y: 85.0,
-        accompanying_text_1: temp_accompanying_text,
-        accompanying_text_2: "".to_string(),
-    });
+        max_y,
+        stacked: false,
+        accompanying_text_1: match hottest_temp {
+            Some(x) => format!("{:.2}°C", x),
+            None => "--".to_string(),
+        },
+        accompanying_text_2: fan_speeds,
+    }
+}
 
-    charts
+pub fn build_cpu_breakdown_chart(entries: &[Option<&AllStats>]) -> ChartContext {
+    let mut user_values = Vec::new();
+    let mut nice_values = Vec::new();
+    let mut system_values = Vec::new();
+    let mut interrupt_values = Vec::new();
+    let mut iowait_values = Vec::new();
+    let mut steal_values = Vec::new();
+    let mut x_values = Vec::new();
+    for &entry in entries {
+        let breakdown = entry.map(|stats| {
+            stats
+                .cpu
+                .as_ref()
+                .and_then(|x| x.aggregate_breakdown)
+                .unwrap_or_default()
+        });
+        user_values.push(breakdown.map(|x| x.user));
+        nice_values.push(breakdown.map(|x| x.nice));
+        system_values.push(breakdown.map(|x| x.system));
+        interrupt_values.push(breakdown.map(|x| x.interrupt));
+        iowait_values.push(breakdown.map(|x| x.iowait));
+        steal_values.push(breakdown.map(|x| x.steal));
+        x_values.push(format_entry_time(entry));
+    }
+
+    let accompanying_text_1 = format!(
+        "{:.2}% user, {:.2}% nice, {:.2}% system",
+        last_value(&user_values),
+        last_value(&nice_values),
+        last_value(&system_values)
+    );
+    let accompanying_text_2 = format!(
+        "{:.2}% interrupt, {:.2}% iowait, {:.2}% steal",
+        last_value(&interrupt_values),
+        last_value(&iowait_values),
+        last_value(&steal_values)
+    );
+    let breakdown_dataset =
+        |name: &str, line_color_code: &str, fill_color_code: &str, values| DatasetContext {
+            name: name.to_string(),
+            line_color_code: line_color_code.to_string(),
+            fill_color_code: fill_color_code.to_string(),
+            values,
+            fill: true,
+        };
+
+    ChartContext {
+        id: "cpu-breakdown-chart".to_string(),
+        title: "CPU Time Breakdown".to_string(),
+        datasets: vec![
+            breakdown_dataset(
+                "User",
+                CPU_USER_LINE_COLOR,
+                CPU_USER_FILL_COLOR,
+                user_values,
+            ),
+            breakdown_dataset(
+                "Nice",
+                CPU_NICE_LINE_COLOR,
+                CPU_NICE_FILL_COLOR,
+                nice_values,
+            ),
+            breakdown_dataset(
+                "System",
+                CPU_SYSTEM_LINE_COLOR,
+                CPU_SYSTEM_FILL_COLOR,
+                system_values,
+            ),
+            breakdown_dataset(
+                "Interrupt",
+                CPU_INTERRUPT_LINE_COLOR,
+                CPU_INTERRUPT_FILL_COLOR,
+                interrupt_values,
+            ),
+            breakdown_dataset(
+                "IOWait",
+                CPU_IOWAIT_LINE_COLOR,
+                CPU_IOWAIT_FILL_COLOR,
+                iowait_values,
+            ),
+            breakdown_dataset(
+                "Steal",
+                CPU_STEAL_LINE_COLOR,
+                CPU_STEAL_FILL_COLOR,
+                steal_values,
+            ),
+        ],
+        x_label: "Time".to_string(),
+        y_label: "Time (%)".to_string(),
+        x_values,
+        min_y: 0.0,
+        max_y: 100.0,
+        stacked: true,
+        accompanying_text_1,
+        accompanying_text_2,
+    }
 }
 
-fn build_memory_chart(stats_history: &StatsHistory) -> ChartContext {
-    let mut memory_values = Vec::new();
+pub fn build_memory_chart(
+    entries: &[Option<&AllStats>],
+    most_recent_stats: &AllStats,
+) -> ChartContext {
+    let mut used_values = Vec::new();
+    let mut cache_values = Vec::new();
+    let mut swap_values = Vec::new();
     let mut memory_total_mb = 0;
+    let mut swap_total_mb = 0;
     let mut x_values = Vec::new();
-    for stats in stats_history.into_iter() {
-        match &stats.memory {
-            Some(x) => {
-                if x.total_mb > memory_total_mb {
-                    memory_total_mb = x.total_mb;
+    for &entry in entries {
+        match entry {
+            Some(stats) => match &stats.memory {
+                Some(x) => {
+                    if x.total_mb > memory_total_mb {
+                        memory_total_mb = x.total_mb;
+                    }
+                    if x.swap_total_mb > swap_total_mb {
+                        swap_total_mb = x.swap_total_mb;
+                    }
+                    used_values.push(Some(x.used_mb as f32));
+                    cache_values.push(Some((x.buffers_mb + x.cached_mb) as f32));
+                    swap_values.push(Some(x.swap_used_mb as f32));
+                }
+                None => {
+                    used_values.push(Some(0.0));
+                    cache_values.push(Some(0.0));
+                    swap_values.push(Some(0.0));
                 }
-                memory_values.push(x.used_mb as f32)
+            },
+            None => {
+                used_values.push(None);
+                cache_values.push(None);
+                swap_values.push(None);
             }
-            None => memory_values.push(0.0),
         }
-        x_values.push(format_time(stats.collection_time));
+        x_values.push(format_entry_time(entry));
     }
 
     let (accompanying_text_1, accompanying_text_2) = {
-        match stats_history.get_most_recent_stats() {
-            Some(x) => match &x.memory {
-                Some(mem) => {
-                    let used_pct = ((mem.used_mb as f64) / (mem.total_mb as f64)) * 100.0;
-                    (
-                        format!("{} / {} MB", mem.used_mb, mem.total_mb),
-                        format!("{:.2}%", used_pct),
-                    )
-                }
-                None => ("-- / -- MB".to_string(), "--%".to_string()),
-            },
+        match &most_recent_stats.memory {
+            Some(mem) => {
+                let used_pct = ((mem.used_mb as f64) / (mem.total_mb as f64)) * 100.0;
+                (
+                    format!("{} / {} MB", mem.used_mb, mem.total_mb),
+                    format!(
+                        "{:.2}%, {} MB cache, {} / {} MB swap",
+                        used_pct,
+                        mem.buffers_mb + mem.cached_mb,
+                        mem.swap_used_mb,
+                        mem.swap_total_mb
+                    ),
+                )
+            }
             None => ("-- / -- MB".to_string(), "--%".to_string()),
         }
     };
@@ -409,50 +1000,77 @@ fn build_memory_chart(stats_history: &StatsHistory) -> ChartContext {
     ChartContext {
         id: "ram-chart".to_string(),
         title: "Memory Usage".to_string(),
-        datasets: vec![DatasetContext {
-            name: "MB Used".to_string(),
-            line_color_code: MEM_LINE_COLOR.to_string(),
-            fill_color_code: MEM_FILL_COLOR.to_string(),
-            values: memory_values,
-            fill: true,
-        }],
+        datasets: vec![
+            DatasetContext {
+                name: "MB Used".to_string(),
+                line_color_code: MEM_LINE_COLOR.to_string(),
+                fill_color_code: MEM_FILL_COLOR.to_string(),
+                values: used_values,
+                fill: true,
+            },
+            DatasetContext {
+                name: "MB Cache".to_string(),
+                line_color_code: MEM_CACHE_LINE_COLOR.to_string(),
+                fill_color_code: MEM_CACHE_FILL_COLOR.to_string(),
+                values: cache_values,
+                fill: true,
+            },
+            DatasetContext {
+                name: "MB Swap Used".to_string(),
+                line_color_code: SWAP_LINE_COLOR.to_string(),
+                fill_color_code: SWAP_FILL_COLOR.to_string(),
+                values: swap_values,
+                fill: true,
+            },
+        ],
         x_label: "Time".to_string(),
         y_label: "Usage (MB)".to_string(),
         x_values,
         min_y: 0.0,
-        max_y: memory_total_mb as f32,
+        max_y: (memory_total_mb + swap_total_mb) as f32,
+        stacked: true,
         accompanying_text_1,
         accompanying_text_2,
     }
 }
 
-fn build_load_average_chart(stats_history: &StatsHistory) -> ChartContext {
+pub fn build_load_average_chart(entries: &[Option<&AllStats>]) -> ChartContext {
     let mut one_min_values = Vec::new();
     let mut five_min_values = Vec::new();
     let mut fifteen_min_values = Vec::new();
     let mut x_values = Vec::new();
-    for stats in stats_history.into_iter() {
-        match &stats.general.load_averages {
-            Some(x) => {
-                one_min_values.push(x.one_minute);
-                five_min_values.push(x.five_minutes);
-                fifteen_min_values.push(x.fifteen_minutes);
+    for &entry in entries {
+        match entry.map(|stats| {
+            stats
+                .general
+                .as_ref()
+                .and_then(|x| x.load_averages.as_ref())
+        }) {
+            Some(Some(x)) => {
+                one_min_values.push(Some(x.one_minute));
+                five_min_values.push(Some(x.five_minutes));
+                fifteen_min_values.push(Some(x.fifteen_minutes));
+            }
+            Some(None) => {
+                one_min_values.push(Some(0.0));
+                five_min_values.push(Some(0.0));
+                fifteen_min_values.push(Some(0.0));
             }
             None => {
-                one_min_values.push(0.0);
-                five_min_values.push(0.0);
-                fifteen_min_values.push(0.0);
+                one_min_values.push(None);
+                five_min_values.push(None);
+                fifteen_min_values.push(None);
             }
         }
 
-        x_values.push(format_time(stats.collection_time));
+        x_values.push(format_entry_time(entry));
     }
 
     let accompanying_text = format!(
         "1: {:.2}, 5: {:.2}, 15: {:.2}",
-        one_min_values.last().unwrap_or(&0.0),
-        five_min_values.last().unwrap_or(&0.0),
-        fifteen_min_values.last().unwrap_or(&0.0)
+        last_value(&one_min_values),
+        last_value(&five_min_values),
+        last_value(&fifteen_min_values)
     );
     let datasets = vec![
         DatasetContext {
@@ -487,101 +1105,308 @@ fn build_load_average_chart(stats_history: &StatsHistory) -> ChartContext {
         x_values,
         min_y: 0.0,
         max_y: 0.0,
+        stacked: false,
         accompanying_text_1: accompanying_text,
         accompanying_text_2: "".to_string(),
     }
 }
 
-fn build_network_charts(stats_history: &StatsHistory) -> Vec<ChartContext> {
-    let mut sent_mb_values = Vec::new();
-    let mut received_mb_values = Vec::new();
+pub fn build_disk_io_charts(
+    entries: &[Option<&AllStats>],
+    most_recent_disk_stats: &[DiskStats],
+) -> Vec<ChartContext> {
+    let x_values: Vec<String> = entries.iter().map(|&x| format_entry_time(x)).collect();
+    let mut charts = Vec::new();
+    for most_recent_disk in most_recent_disk_stats {
+        let name = &most_recent_disk.name;
+        let mut read_kb_values = Vec::new();
+        let mut written_kb_values = Vec::new();
+        for &entry in entries {
+            let disk_stats = entry
+                .and_then(|stats| stats.disks.as_ref())
+                .and_then(|x| x.iter().find(|disk| &disk.name == name));
+            match disk_stats {
+                Some(x) => {
+                    read_kb_values.push(x.read_bytes_per_second.map(|rate| rate / BYTES_PER_KB));
+                    written_kb_values
+                        .push(x.written_bytes_per_second.map(|rate| rate / BYTES_PER_KB));
+                }
+                None => {
+                    read_kb_values.push(None);
+                    written_kb_values.push(None);
+                }
+            }
+        }
+
+        let accompanying_text_1 = format!(
+            "{:.2} KB/s read, {:.2} KB/s written",
+            last_value(&read_kb_values),
+            last_value(&written_kb_values)
+        );
+        let accompanying_text_2 = format!(
+            "{:.2} IOPS, {:.2}% busy, {:.2} queue depth",
+            most_recent_disk.reads_per_second.unwrap_or(0.0)
+                + most_recent_disk.writes_per_second.unwrap_or(0.0),
+            most_recent_disk.busy_percent.unwrap_or(0.0),
+            most_recent_disk.average_queue_depth.unwrap_or(0.0)
+        );
+        let datasets = vec![
+            DatasetContext {
+                name: "Read".to_string(),
+                line_color_code: DISK_READ_LINE_COLOR.to_string(),
+                fill_color_code: DISK_READ_FILL_COLOR.to_string(),
+                values: read_kb_values,
+                fill: false,
+            },
+            DatasetContext {
+                name: "Written".to_string(),
+                line_color_code: DISK_WRITE_LINE_COLOR.to_string(),
+                fill_color_code: DISK_WRITE_FILL_COLOR.to_string(),
+                values: written_kb_values,
+                fill: false,
+            },
+        ];
+
+        charts.push(ChartContext {
+            id: format!("{}{}", DISK_IO_CHART_ID_PREFIX, name),
+            title: format!("Disk I/O ({})", name),
+            datasets,
+            x_label: "Time".to_string(),
+            y_label: "Throughput (KB/s)".to_string(),
+            x_values: x_values.clone(),
+            min_y: 0.0,
+            max_y: 0.0,
+            stacked: false,
+            accompanying_text_1,
+            accompanying_text_2,
+        });
+    }
+
+    charts
+}
+
+pub fn build_pressure_chart(entries: &[Option<&AllStats>]) -> ChartContext {
+    let mut cpu_values = Vec::new();
+    let mut memory_values = Vec::new();
+    let mut io_values = Vec::new();
+    let mut x_values = Vec::new();
+    let mut most_recent_pressure = None;
+    for &entry in entries {
+        match entry.map(|stats| stats.general.as_ref().and_then(|x| x.pressure.as_ref())) {
+            Some(Some(x)) => {
+                cpu_values.push(Some(x.cpu.some.avg10));
+                memory_values.push(Some(x.memory.some.avg10));
+                io_values.push(Some(x.io.some.avg10));
+                most_recent_pressure = Some(x);
+            }
+            Some(None) => {
+                cpu_values.push(Some(0.0));
+                memory_values.push(Some(0.0));
+                io_values.push(Some(0.0));
+            }
+            None => {
+                cpu_values.push(None);
+                memory_values.push(None);
+                io_values.push(None);
+            }
+        }
+        x_values.push(format_entry_time(entry));
+    }
+
+    let accompanying_text_1 = format!(
+        "Some: CPU {:.2}%, memory {:.2}%, I/O {:.2}%",
+        last_value(&cpu_values),
+        last_value(&memory_values),
+        last_value(&io_values)
+    );
+    let accompanying_text_2 = match most_recent_pressure {
+        Some(x) => format!(
+            "Full: memory {:.2}%, I/O {:.2}%",
+            x.memory.full.unwrap_or_default().avg10,
+            x.io.full.unwrap_or_default().avg10
+        ),
+        None => "".to_string(),
+    };
+    let datasets = vec![
+        DatasetContext {
+            name: "CPU".to_string(),
+            line_color_code: CPU_PRESSURE_LINE_COLOR.to_string(),
+            fill_color_code: CPU_PRESSURE_FILL_COLOR.to_string(),
+            values: cpu_values,
+            fill: false,
+        },
+        DatasetContext {
+            name: "Memory".to_string(),
+            line_color_code: MEMORY_PRESSURE_LINE_COLOR.to_string(),
+            fill_color_code: MEMORY_PRESSURE_FILL_COLOR.to_string(),
+            values: memory_values,
+            fill: false,
+        },
+        DatasetContext {
+            name: "I/O".to_string(),
+            line_color_code: IO_PRESSURE_LINE_COLOR.to_string(),
+            fill_color_code: IO_PRESSURE_FILL_COLOR.to_string(),
+            values: io_values,
+            fill: false,
+        },
+    ];
+
+    ChartContext {
+        id: "pressure-chart".to_string(),
+        title: "Pressure Stall (10 second average)".to_string(),
+        datasets,
+        x_label: "Time".to_string(),
+        y_label: "Time stalled (%)".to_string(),
+        x_values,
+        min_y: 0.0,
+        max_y: 0.0,
+        stacked: false,
+        accompanying_text_1,
+        accompanying_text_2,
+    }
+}
+
+pub fn build_network_throughput_charts(
+    entries: &[Option<&AllStats>],
+    most_recent_network_stats: &NetworkStats,
+) -> Vec<ChartContext> {
+    let interface_names = match &most_recent_network_stats.interfaces {
+        Some(x) => x.iter().map(|interface| interface.name.clone()),
+        None => return Vec::new(),
+    };
+
+    let x_values: Vec<String> = entries.iter().map(|&x| format_entry_time(x)).collect();
+    let mut charts = Vec::new();
+    for name in interface_names {
+        let mut sent_kb_values = Vec::new();
+        let mut received_kb_values = Vec::new();
+        let mut sent_packets_values = Vec::new();
+        let mut received_packets_values = Vec::new();
+        for &entry in entries {
+            let interface_stats = entry
+                .and_then(|stats| stats.network.as_ref())
+                .and_then(|x| x.interfaces.as_ref())
+                .and_then(|x| x.iter().find(|interface| interface.name == name));
+            match interface_stats {
+                Some(x) => {
+                    sent_kb_values.push(x.sent_bytes_per_second.map(|rate| rate / BYTES_PER_KB));
+                    received_kb_values
+                        .push(x.received_bytes_per_second.map(|rate| rate / BYTES_PER_KB));
+                    sent_packets_values.push(x.sent_packets_per_second);
+                    received_packets_values.push(x.received_packets_per_second);
+                }
+                None => {
+                    sent_kb_values.push(None);
+                    received_kb_values.push(None);
+                    sent_packets_values.push(None);
+                    received_packets_values.push(None);
+                }
+            }
+        }
+
+        let accompanying_text_1 = format!(
+            "{:.2} KB/s sent, {:.2} KB/s received",
+            last_value(&sent_kb_values),
+            last_value(&received_kb_values)
+        );
+        let accompanying_text_2 = format!(
+            "{:.2} packets/s sent, {:.2} packets/s received",
+            last_value(&sent_packets_values),
+            last_value(&received_packets_values)
+        );
+        let datasets = vec![
+            DatasetContext {
+                name: "Sent".to_string(),
+                line_color_code: SENT_LINE_COLOR.to_string(),
+                fill_color_code: SENT_FILL_COLOR.to_string(),
+                values: sent_kb_values,
+                fill: false,
+            },
+            DatasetContext {
+                name: "Received".to_string(),
+                line_color_code: RECEIVED_LINE_COLOR.to_string(),
+                fill_color_code: RECEIVED_FILL_COLOR.to_string(),
+                values: received_kb_values,
+                fill: false,
+            },
+        ];
+
+        charts.push(ChartContext {
+            id: format!("{}{}", NETWORK_THROUGHPUT_CHART_ID_PREFIX, name),
+            title: format!("Network Throughput ({})", name),
+            datasets,
+            x_label: "Time".to_string(),
+            y_label: "Throughput (KB/s)".to_string(),
+            x_values: x_values.clone(),
+            min_y: 0.0,
+            max_y: 0.0,
+            stacked: false,
+            accompanying_text_1,
+            accompanying_text_2,
+        });
+    }
+
+    charts
+}
+
+pub fn build_network_charts(entries: &[Option<&AllStats>]) -> Vec<ChartContext> {
     let mut send_errors_values = Vec::new();
     let mut receive_errors_values = Vec::new();
     let mut tcp_sockets_values = Vec::new();
     let mut udp_sockets_values = Vec::new();
     let mut x_values = Vec::new();
-    for stats in stats_history.into_iter() {
-        match &stats.network.interfaces {
+    for &entry in entries {
+        let stats = match entry {
+            Some(x) => x,
+            None => {
+                send_errors_values.push(None);
+                receive_errors_values.push(None);
+                tcp_sockets_values.push(None);
+                udp_sockets_values.push(None);
+                x_values.push(format_entry_time(entry));
+                continue;
+            }
+        };
+
+        let network = stats.network.as_ref();
+        match network.and_then(|x| x.interfaces.as_ref()) {
             Some(x) => {
-                let mut total_sent_mb = 0.0;
-                let mut total_received_mb = 0.0;
                 let mut total_send_errors = 0.0;
                 let mut total_receive_errors = 0.0;
                 for interface_stats in x {
-                    total_sent_mb += interface_stats.sent_mb as f32;
-                    total_received_mb += interface_stats.received_mb as f32;
                     total_send_errors += interface_stats.send_errors as f32;
                     total_receive_errors += interface_stats.receive_errors as f32;
                 }
 
-                sent_mb_values.push(total_sent_mb);
-                received_mb_values.push(total_received_mb);
-                send_errors_values.push(total_send_errors);
-                receive_errors_values.push(total_receive_errors);
+                send_errors_values.push(Some(total_send_errors));
+                receive_errors_values.push(Some(total_receive_errors));
             }
             None => {
-                sent_mb_values.push(0.0);
-                received_mb_values.push(0.0);
-                send_errors_values.push(0.0);
-                receive_errors_values.push(0.0);
+                send_errors_values.push(Some(0.0));
+                receive_errors_values.push(Some(0.0));
             }
         }
 
-        match &stats.network.sockets {
+        match network.and_then(|x| x.sockets.as_ref()) {
             Some(x) => {
-                tcp_sockets_values.push(x.tcp_in_use as f32);
-                udp_sockets_values.push(x.udp_in_use as f32);
+                tcp_sockets_values.push(Some(x.tcp_in_use as f32));
+                udp_sockets_values.push(Some(x.udp_in_use as f32));
             }
             None => {
-                tcp_sockets_values.push(0.0);
-                udp_sockets_values.push(0.0);
+                tcp_sockets_values.push(Some(0.0));
+                udp_sockets_values.push(Some(0.0));
             }
         }
 
-        x_values.push(format_time(stats.collection_time));
+        x_values.push(format_entry_time(entry));
     }
 
     let mut charts = Vec::new();
 
-    let usage_accompanying_text = format!(
-        "{} MB sent, {} MB received",
-        sent_mb_values.last().unwrap_or(&0.0),
-        received_mb_values.last().unwrap_or(&0.0)
-    );
-    let usage_datasets = vec![
-        DatasetContext {
-            name: "Sent".to_string(),
-            line_color_code: SENT_LINE_COLOR.to_string(),
-            fill_color_code: SENT_FILL_COLOR.to_string(),
-            values: sent_mb_values,
-            fill: false,
-        },
-        DatasetContext {
-            name: "Received".to_string(),
-            line_color_code: RECEIVED_LINE_COLOR.to_string(),
-            fill_color_code: RECEIVED_FILL_COLOR.to_string(),
-            values: received_mb_values,
-            fill: false,
-        },
-    ];
-
-    charts.push(ChartContext {
-        id: "network-usage-chart".to_string(),
-        title: "Cumulative Network Usage".to_string(),
-        datasets: usage_datasets,
-        x_label: "Time".to_string(),
-        y_label: "Total (MB)".to_string(),
-        x_values: x_values.clone(),
-        min_y: 0.0,
-        max_y: 0.0,
-        accompanying_text_1: usage_accompanying_text,
-        accompanying_text_2: "".to_string(),
-    });
-
     let errors_accompanying_text = format!(
         "{} send, {} receive",
-        send_errors_values.last().unwrap_or(&0.0),
-        receive_errors_values.last().unwrap_or(&0.0)
+        last_value(&send_errors_values),
+        last_value(&receive_errors_values)
     );
     let errors_datasets = vec![
         DatasetContext {
@@ -609,14 +1434,15 @@ fn build_network_charts(stats_history: &StatsHistory) -> Vec<ChartContext> {
         x_values: x_values.clone(),
         min_y: 0.0,
         max_y: 0.0,
+        stacked: false,
         accompanying_text_1: errors_accompanying_text,
         accompanying_text_2: "".to_string(),
     });
 
     let sockets_accompanying_text = format!(
         "{} TCP, {} UDP",
-        tcp_sockets_values.last().unwrap_or(&0.0),
-        udp_sockets_values.last().unwrap_or(&0.0)
+        last_value(&tcp_sockets_values),
+        last_value(&udp_sockets_values)
     );
     let sockets_datasets = vec![
         DatasetContext {
@@ -644,6 +1470,7 @@ fn build_network_charts(stats_history: &StatsHistory) -> Vec<ChartContext> {
         x_values,
         min_y: 0.0,
         max_y: 0.0,
+        stacked: false,
         accompanying_text_1: sockets_accompanying_text,
         accompanying_text_2: "".to_string(),
     });
@@ -651,6 +1478,137 @@ fn build_network_charts(stats_history: &StatsHistory) -> Vec<ChartContext> {
     charts
 }
 
+pub fn build_tcp_states_chart(entries: &[Option<&AllStats>]) -> ChartContext {
+    let mut established_values = Vec::new();
+    let mut time_wait_values = Vec::new();
+    let mut close_wait_values = Vec::new();
+    let mut x_values = Vec::new();
+    let mut most_recent_listening = 0;
+    for &entry in entries {
+        match entry.map(|stats| {
+            stats
+                .network
+                .as_ref()
+                .and_then(|x| x.sockets.as_ref())
+                .and_then(combined_tcp_states)
+        }) {
+            Some(Some(x)) => {
+                established_values.push(Some(x.established as f32));
+                time_wait_values.push(Some(x.time_wait as f32));
+                close_wait_values.push(Some(x.close_wait as f32));
+                most_recent_listening = x.listen;
+            }
+            Some(None) => {
+                established_values.push(Some(0.0));
+                time_wait_values.push(Some(0.0));
+                close_wait_values.push(Some(0.0));
+            }
+            None => {
+                established_values.push(None);
+                time_wait_values.push(None);
+                close_wait_values.push(None);
+            }
+        }
+        x_values.push(format_entry_time(entry));
+    }
+
+    let accompanying_text_1 = format!(
+        "{} established, {} time wait, {} close wait",
+        last_value(&established_values),
+        last_value(&time_wait_values),
+        last_value(&close_wait_values)
+    );
+    let accompanying_text_2 = format!("{} listening", most_recent_listening);
+    let datasets = vec![
+        DatasetContext {
+            name: "Established".to_string(),
+            line_color_code: TCP_ESTABLISHED_LINE_COLOR.to_string(),
+            fill_color_code: TCP_ESTABLISHED_FILL_COLOR.to_string(),
+            values: established_values,
+            fill: false,
+        },
+        DatasetContext {
+            name: "Time wait".to_string(),
+            line_color_code: TCP_TIME_WAIT_LINE_COLOR.to_string(),
+            fill_color_code: TCP_TIME_WAIT_FILL_COLOR.to_string(),
+            values: time_wait_values,
+            fill: false,
+        },
+        DatasetContext {
+            name: "Close wait".to_string(),
+            line_color_code: TCP_CLOSE_WAIT_LINE_COLOR.to_string(),
+            fill_color_code: TCP_CLOSE_WAIT_FILL_COLOR.to_string(),
+            values: close_wait_values,
+            fill: false,
+        },
+    ];
+
+    ChartContext {
+        id: "tcp-states-chart".to_string(),
+        title: "TCP Connection States".to_string(),
+        datasets,
+        x_label: "Time".to_string(),
+        y_label: "Connections".to_string(),
+        x_values,
+        min_y: 0.0,
+        max_y: 0.0,
+        stacked: false,
+        accompanying_text_1,
+        accompanying_text_2,
+    }
+}
+
+/// Adds up the IPv4 and IPv6 TCP connection state counts. Returns `None` if neither are available.
+pub fn combined_tcp_states(socket_stats: &SocketStats) -> Option<TcpStateCounts> {
+    if socket_stats.tcp_states.is_none() && socket_stats.tcp6_states.is_none() {
+        return None;
+    }
+
+    let mut states = TcpStateCounts::default();
+    for x in socket_stats
+        .tcp_states
+        .iter()
+        .chain(socket_stats.tcp6_states.iter())
+    {
+        states.add(x);
+    }
+
+    Some(states)
+}
+
+/// Gets the entries in the provided stats history from oldest to newest, consolidated with the configured strategy, and with `None` inserted wherever more than the configured maximum gap passed between consecutive entries.
+fn entries_with_gaps<'a>(
+    stats_history: &'a StatsHistory,
+    config: &DashboardConfig,
+) -> Vec<Option<&'a AllStats>> {
+    let mut entries = Vec::new();
+    let mut previous_collection_time = None;
+    for stats in stats_history {
+        if let Some(previous) = previous_collection_time {
+            if stats.collection_time - previous > config.max_gap {
+                entries.push(None);
+            }
+        }
+        entries.push(Some(stats.consolidated_with(config.chart_consolidation)));
+        previous_collection_time = Some(stats.collection_time);
+    }
+
+    entries
+}
+
+/// Gets the last value in the provided list of values, or 0 if there isn't one.
+fn last_value(values: &[Option<f32>]) -> f32 {
+    values.last().copied().flatten().unwrap_or(0.0)
+}
+
+/// Gets the X axis label for an entry. Gaps have empty labels.
+fn format_entry_time(entry: Option<&AllStats>) -> String {
+    match entry {
+        Some(stats) => format_time(stats.collection_time),
+        None => "".to_string(),
+    }
+}
+
 fn format_time(time: DateTime<Local>) -> String {
     time.format("%I:%M:%S %p").to_string()
 }
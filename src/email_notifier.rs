@@ -0,0 +1,447 @@
+//! Email notifications for alerts.
+
+use std::{
+    fmt,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::SecondsFormat;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use serde::Deserialize;
+
+use crate::alerts::{AlertNotification, AlertNotifier, AlertState};
+use crate::stats::AllStats;
+
+const DEFAULT_BATCH_SECONDS: u64 = 60;
+const DEFAULT_MAX_BATCH_SECONDS: u64 = 3600;
+
+/// How to secure the connection to an SMTP server.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// The connection is not encrypted.
+    None,
+    /// The connection is upgraded to an encrypted one with STARTTLS.
+    StartTls,
+}
+
+/// Configuration for sending alert notifications via email.
+#[derive(Clone, Deserialize)]
+pub struct EmailConfig {
+    /// The host name of the SMTP server.
+    pub host: String,
+    /// The port of the SMTP server.
+    pub port: u16,
+    /// How to secure the connection to the SMTP server.
+    pub security: SmtpSecurity,
+    /// The user name to authenticate to the SMTP server with, if any.
+    pub username: Option<String>,
+    /// The password to authenticate to the SMTP server with, if any.
+    pub password: Option<String>,
+    /// The address to send emails from.
+    pub from: String,
+    /// The addresses to send emails to.
+    pub to: Vec<String>,
+    /// The number of seconds to wait after a notification before sending an email, so notifications that happen close together are sent in a single email.
+    #[serde(default = "default_batch_seconds")]
+    pub batch_seconds: u64,
+    /// The longest number of seconds to wait before sending an email. While an alert keeps changing state more than once per email, the wait doubles after each email, up to this.
+    #[serde(default = "default_max_batch_seconds")]
+    pub max_batch_seconds: u64,
+}
+
+fn default_batch_seconds() -> u64 {
+    DEFAULT_BATCH_SECONDS
+}
+
+fn default_max_batch_seconds() -> u64 {
+    DEFAULT_MAX_BATCH_SECONDS
+}
+
+impl fmt::Debug for EmailConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the password is left out so it doesn't end up in logs
+        f.debug_struct("EmailConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("security", &self.security)
+            .field("username", &self.username)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("batch_seconds", &self.batch_seconds)
+            .field("max_batch_seconds", &self.max_batch_seconds)
+            .finish()
+    }
+}
+
+/// Sends alert notifications via email. Emails are sent from a separate thread.
+pub struct EmailNotifier {
+    /// Sends notifications to the thread that sends emails.
+    sender: Sender<AlertNotification>,
+}
+
+impl EmailNotifier {
+    /// Creates an `EmailNotifier` and starts the thread that sends emails.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration for sending emails.
+    pub fn new(config: EmailConfig) -> Result<EmailNotifier, String> {
+        let transport = build_transport(&config)?;
+        let from: Mailbox = config
+            .from
+            .parse()
+            .map_err(|e| format!("invalid from address {}: {}", config.from, e))?;
+        let to = config
+            .to
+            .iter()
+            .map(|address| {
+                address
+                    .parse()
+                    .map_err(|e| format!("invalid to address {}: {}", address, e))
+            })
+            .collect::<Result<Vec<Mailbox>, String>>()?;
+        if to.is_empty() {
+            return Err("at least one to address is required".to_string());
+        }
+
+        let (sender, receiver) = channel();
+        let min_batch_duration = Duration::from_secs(config.batch_seconds);
+        let max_batch_duration =
+            Duration::from_secs(config.max_batch_seconds).max(min_batch_duration);
+        let mut batch_duration = min_batch_duration;
+        thread::spawn(move || loop {
+            let batch = match receive_batch(&receiver, batch_duration) {
+                Some(x) => x,
+                None => return,
+            };
+
+            // back off while an alert is flapping, so it doesn't keep sending an email every batch
+            batch_duration = if has_flapping_alert(&batch) {
+                (batch_duration * 2).min(max_batch_duration)
+            } else {
+                min_batch_duration
+            };
+
+            let email = match build_email(&from, &to, &batch) {
+                Ok(x) => x,
+                Err(e) => {
+                    println!("Error building alert notification email: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = transport.send(&email) {
+                println!("Error sending alert notification email: {}", e);
+            }
+        });
+
+        Ok(EmailNotifier { sender })
+    }
+}
+
+impl AlertNotifier for EmailNotifier {
+    fn notify(&self, notification: AlertNotification) {
+        if self.sender.send(notification).is_err() {
+            println!("Error sending alert notification email: email thread has stopped");
+        }
+    }
+}
+
+/// Builds the transport to send emails with.
+fn build_transport(config: &EmailConfig) -> Result<SmtpTransport, String> {
+    let builder = match config.security {
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.host),
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&config.host)
+            .map_err(|e| format!("error setting up STARTTLS for {}: {}", config.host, e))?,
+    };
+    let builder = builder.port(config.port);
+    let builder = match (&config.username, &config.password) {
+        (Some(username), Some(password)) => {
+            builder.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => builder,
+    };
+
+    Ok(builder.build())
+}
+
+/// Waits for a notification, then collects any other notifications received within `batch_duration` of it. Returns `None` if no more notifications will be received.
+fn receive_batch(
+    receiver: &Receiver<AlertNotification>,
+    batch_duration: Duration,
+) -> Option<Vec<AlertNotification>> {
+    let mut batch = vec![receiver.recv().ok()?];
+    let deadline = Instant::now() + batch_duration;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(x) => batch.push(x),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Some(batch)
+}
+
+/// Determines whether any alert changed state more than once in the provided notifications.
+fn has_flapping_alert(notifications: &[AlertNotification]) -> bool {
+    notifications.iter().enumerate().any(|(i, notification)| {
+        notifications[..i]
+            .iter()
+            .any(|x| x.status.rule.name == notification.status.rule.name)
+    })
+}
+
+/// Builds an email describing the provided notifications. If an alert changed state more than once, only its most recent state is described.
+fn build_email(
+    from: &Mailbox,
+    to: &[Mailbox],
+    notifications: &[AlertNotification],
+) -> Result<Message, String> {
+    // keep only the latest notification for each rule, in the order the rules first appeared
+    let mut latest_notifications: Vec<(&AlertNotification, usize)> = Vec::new();
+    for notification in notifications {
+        match latest_notifications
+            .iter_mut()
+            .find(|(x, _)| x.status.rule.name == notification.status.rule.name)
+        {
+            Some(entry) => *entry = (notification, entry.1 + 1),
+            None => latest_notifications.push((notification, 1)),
+        }
+    }
+
+    let firing_count = latest_notifications
+        .iter()
+        .filter(|(x, _)| x.status.state == AlertState::Firing)
+        .count();
+    let resolved_count = latest_notifications.len() - firing_count;
+    let subject = match latest_notifications.as_slice() {
+        [(notification, _)] => format!(
+            "Alert {}: {}",
+            describe_state(notification.status.state),
+            notification.status.rule.name
+        ),
+        _ => format!(
+            "{} alerts firing, {} resolved",
+            firing_count, resolved_count
+        ),
+    };
+
+    let mut body = String::new();
+    for (notification, count) in &latest_notifications {
+        let status = &notification.status;
+        body.push_str(&format!(
+            "{} is {}\n",
+            status.rule.name,
+            describe_state(status.state)
+        ));
+        let value = match status.value {
+            Some(x) => format!("{:.2}", x),
+            None => "no value".to_string(),
+        };
+        body.push_str(&format!(
+            "  {} is {}, threshold is {} {} for {} seconds\n",
+            status.rule.metric,
+            value,
+            status.rule.comparator.symbol(),
+            status.rule.threshold,
+            status.rule.for_seconds
+        ));
+        if *count > 1 {
+            body.push_str(&format!(
+                "  (changed state {} times since the last email)\n",
+                count
+            ));
+        }
+        body.push('\n');
+    }
+
+    // this won't panic because there's always at least one notification in a batch
+    let most_recent_stats = &notifications.last().unwrap().stats;
+    body.push_str(&summarize_stats(most_recent_stats));
+
+    let mut builder = Message::builder().from(from.clone()).subject(subject);
+    for address in to {
+        builder = builder.to(address.clone());
+    }
+
+    builder.body(body).map_err(|e| e.to_string())
+}
+
+/// Gets a word describing an alert state.
+fn describe_state(state: AlertState) -> &'static str {
+    match state {
+        AlertState::Inactive => "inactive",
+        AlertState::Pending => "pending",
+        AlertState::Firing => "firing",
+        AlertState::Resolved => "resolved",
+    }
+}
+
+/// Builds a short plain text summary of the provided stats.
+fn summarize_stats(stats: &AllStats) -> String {
+    let mut lines = vec![format!(
+        "Stats collected at {}:",
+        stats
+            .collection_time
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    )];
+
+    if let Some(x) = stats.cpu.as_ref().and_then(|x| x.aggregate_load_percent) {
+        lines.push(format!("  CPU usage: {:.2}%", x));
+    }
+    if let Some(x) = stats.cpu.as_ref().and_then(|x| x.temp_celsius) {
+        lines.push(format!("  CPU temperature: {:.2}°C", x));
+    }
+    if let Some(x) = stats
+        .general
+        .as_ref()
+        .and_then(|x| x.load_averages.as_ref())
+    {
+        lines.push(format!(
+            "  Load averages: 1: {:.2}, 5: {:.2}, 15: {:.2}",
+            x.one_minute, x.five_minutes, x.fifteen_minutes
+        ));
+    }
+    if let Some(x) = &stats.memory {
+        lines.push(format!("  Memory used: {} / {} MB", x.used_mb, x.total_mb));
+    }
+    if let Some(mounts) = &stats.filesystems {
+        for mount in mounts {
+            lines.push(format!(
+                "  {} used: {} / {} MB",
+                mount.mounted_on, mount.used_mb, mount.total_mb
+            ));
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    use chrono::Local;
+
+    use super::*;
+    use crate::alerts::{AlertRule, Alerts, Comparator};
+    use crate::stats::CpuStats;
+
+    /// Accepts SMTP connections on the provided listener forever, sending the data of each received email to `emails`.
+    fn run_smtp_sink(listener: TcpListener, emails: mpsc::Sender<String>) {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            reply(&mut stream, "220 localhost ESMTP");
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let command = line.trim_end().to_uppercase();
+                if command.starts_with("EHLO") {
+                    reply(&mut stream, "250 localhost");
+                } else if command == "DATA" {
+                    reply(&mut stream, "354 go ahead");
+                    let mut data = String::new();
+                    loop {
+                        let mut data_line = String::new();
+                        reader.read_line(&mut data_line).unwrap();
+                        if data_line == ".\r\n" {
+                            break;
+                        }
+                        data.push_str(&data_line);
+                    }
+                    emails.send(data).unwrap();
+                    reply(&mut stream, "250 OK");
+                } else if command == "QUIT" {
+                    reply(&mut stream, "221 bye");
+                    break;
+                } else {
+                    reply(&mut stream, "250 OK");
+                }
+                line.clear();
+            }
+        }
+    }
+
+    fn reply(stream: &mut TcpStream, message: &str) {
+        stream
+            .write_all(format!("{}\r\n", message).as_bytes())
+            .unwrap();
+    }
+
+    fn stats_with_cpu_load(load: f32) -> AllStats {
+        let mut stats = AllStats::empty(Local::now());
+        stats.cpu = Some(CpuStats {
+            aggregate_load_percent: Some(load),
+            ..CpuStats::default()
+        });
+        stats
+    }
+
+    #[test]
+    fn flapping_alert_is_sent_in_one_email_and_backs_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (email_sender, email_receiver) = mpsc::channel();
+        thread::spawn(move || run_smtp_sink(listener, email_sender));
+
+        let notifier = EmailNotifier::new(EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "dashboard@example.com".to_string(),
+            to: vec!["admin@example.com".to_string()],
+            batch_seconds: 1,
+            max_batch_seconds: 2,
+        })
+        .unwrap();
+        let rule = AlertRule {
+            name: "High CPU usage".to_string(),
+            metric: "cpu.aggregateLoadPercent".to_string(),
+            comparator: Comparator::GreaterThan,
+            threshold: 90.0,
+            for_seconds: 0,
+        };
+        let mut alerts = Alerts::new(vec![rule], vec![Box::new(notifier)]);
+
+        for load in &[95.0, 50.0, 95.0] {
+            alerts.evaluate(&stats_with_cpu_load(*load));
+        }
+
+        let email = email_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(email.contains("Subject: Alert firing: High CPU usage"));
+        assert!(email.contains("changed state 3 times since the last email"));
+        assert!(email_receiver.recv_timeout(Duration::from_secs(2)).is_err());
+
+        // the alert flapped, so the next email waits twice as long
+        let start = Instant::now();
+        for load in &[50.0, 95.0] {
+            alerts.evaluate(&stats_with_cpu_load(*load));
+        }
+        let email = email_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert!(email.contains("changed state 2 times since the last email"));
+
+        // it flapped again, but the wait doesn't double past the maximum
+        let start = Instant::now();
+        alerts.evaluate(&stats_with_cpu_load(50.0));
+        email_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,162 @@
+//! Filters for choosing which mounts and network interfaces are included in stats.
+
+use std::collections::HashSet;
+
+use glob::{Pattern, PatternError};
+use serde::Deserialize;
+
+use crate::stats::{AllStats, MountStats, NetworkInterfaceStats};
+
+/// Filters for choosing which parts of the system are included in stats.
+#[derive(Debug, Clone, Default)]
+pub struct StatsFilters {
+    /// The filter for mounted filesystems.
+    pub mounts: MountFilter,
+    /// The filter for network interfaces.
+    pub interfaces: InterfaceFilter,
+}
+
+impl StatsFilters {
+    /// Removes the mounts and network interfaces that shouldn't be included from the provided stats.
+    pub fn apply(&self, stats: &mut AllStats) {
+        if let Some(mounts) = &mut stats.filesystems {
+            self.mounts.apply(mounts);
+        }
+        if let Some(interfaces) = stats.network.as_mut().and_then(|x| x.interfaces.as_mut()) {
+            self.interfaces.apply(interfaces);
+        }
+    }
+}
+
+/// Configuration for choosing which mounted filesystems are included in stats. Empty include lists include everything, and excludes are applied after includes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MountFilterConfig {
+    /// The filesystem types to include, such as `ext4`.
+    #[serde(default)]
+    pub include_fs_types: Vec<String>,
+    /// The filesystem types to exclude, such as `tmpfs`.
+    #[serde(default)]
+    pub exclude_fs_types: Vec<String>,
+    /// Glob patterns for the paths of mount points to include, such as `/home*`.
+    #[serde(default)]
+    pub include_mount_points: Vec<String>,
+    /// Glob patterns for the paths of mount points to exclude, such as `/snap/*`.
+    #[serde(default)]
+    pub exclude_mount_points: Vec<String>,
+    /// Glob patterns for the devices to include, such as `/dev/sd*`.
+    #[serde(default)]
+    pub include_devices: Vec<String>,
+    /// Glob patterns for the devices to exclude, such as `/dev/loop*`.
+    #[serde(default)]
+    pub exclude_devices: Vec<String>,
+    /// Whether to only include the first mount of each device, so bind mounts aren't counted more than once.
+    #[serde(default)]
+    pub deduplicate_devices: bool,
+}
+
+/// Chooses which mounted filesystems are included in stats.
+#[derive(Debug, Clone, Default)]
+pub struct MountFilter {
+    include_fs_types: Vec<String>,
+    exclude_fs_types: Vec<String>,
+    include_mount_points: Vec<Pattern>,
+    exclude_mount_points: Vec<Pattern>,
+    include_devices: Vec<Pattern>,
+    exclude_devices: Vec<Pattern>,
+    deduplicate_devices: bool,
+}
+
+impl MountFilter {
+    /// Creates a `MountFilter` from the provided configuration. Returns an error if any of the glob patterns are invalid.
+    pub fn new(config: MountFilterConfig) -> Result<MountFilter, PatternError> {
+        Ok(MountFilter {
+            include_fs_types: config.include_fs_types,
+            exclude_fs_types: config.exclude_fs_types,
+            include_mount_points: parse_patterns(&config.include_mount_points)?,
+            exclude_mount_points: parse_patterns(&config.exclude_mount_points)?,
+            include_devices: parse_patterns(&config.include_devices)?,
+            exclude_devices: parse_patterns(&config.exclude_devices)?,
+            deduplicate_devices: config.deduplicate_devices,
+        })
+    }
+
+    /// Removes the mounts that shouldn't be included from the provided list.
+    pub fn apply(&self, mounts: &mut Vec<MountStats>) {
+        let mut seen_devices = HashSet::new();
+        mounts.retain(|mount| {
+            if !self.includes(mount) {
+                return false;
+            }
+            // pseudo filesystems like tmpfs use their type as the device, so only actual device paths are deduplicated
+            if self.deduplicate_devices && mount.mounted_from.starts_with('/') {
+                return seen_devices.insert(mount.mounted_from.clone());
+            }
+
+            true
+        });
+    }
+
+    /// Whether the provided mount matches the includes and excludes.
+    fn includes(&self, mount: &MountStats) -> bool {
+        let fs_type_included = included(&self.include_fs_types, &self.exclude_fs_types, |x| {
+            *x == mount.fs_type
+        });
+        let mount_point_included = included(
+            &self.include_mount_points,
+            &self.exclude_mount_points,
+            |x| x.matches(&mount.mounted_on),
+        );
+        let device_included = included(&self.include_devices, &self.exclude_devices, |x| {
+            x.matches(&mount.mounted_from)
+        });
+
+        fs_type_included && mount_point_included && device_included
+    }
+}
+
+/// Configuration for choosing which network interfaces are included in stats. An empty include list includes everything, and excludes are applied after includes.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct InterfaceFilterConfig {
+    /// Glob patterns for the names of interfaces to include, such as `eth*`.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns for the names of interfaces to exclude, such as `veth*`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Chooses which network interfaces are included in stats.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl InterfaceFilter {
+    /// Creates an `InterfaceFilter` from the provided configuration. Returns an error if any of the glob patterns are invalid.
+    pub fn new(config: InterfaceFilterConfig) -> Result<InterfaceFilter, PatternError> {
+        Ok(InterfaceFilter {
+            include: parse_patterns(&config.include)?,
+            exclude: parse_patterns(&config.exclude)?,
+        })
+    }
+
+    /// Whether the interface with the provided name should be included.
+    pub fn includes(&self, name: &str) -> bool {
+        included(&self.include, &self.exclude, |x| x.matches(name))
+    }
+
+    /// Removes the interfaces that shouldn't be included from the provided list.
+    pub fn apply(&self, interfaces: &mut Vec<NetworkInterfaceStats>) {
+        interfaces.retain(|interface| self.includes(&interface.name));
+    }
+}
+
+/// Whether a value should be included, given lists of includes and excludes and a function to check if one of them matches the value. An empty list of includes matches everything.
+fn included<T>(includes: &[T], excludes: &[T], matches: impl Fn(&T) -> bool) -> bool {
+    (includes.is_empty() || includes.iter().any(&matches)) && !excludes.iter().any(&matches)
+}
+
+fn parse_patterns(patterns: &[String]) -> Result<Vec<Pattern>, PatternError> {
+    patterns.iter().map(|x| Pattern::new(x)).collect()
+}
@@ -0,0 +1,448 @@
+//! Lower resolution tiers of persisted stats history, each consolidated from the tier below it.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use crate::collectors::CollectorRegistry;
+use crate::stats::AllStats;
+use crate::stats_history::*;
+
+/// Configuration for a tier of persisted stats history.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryTierConfig {
+    /// The number of seconds of stats to consolidate into each entry in the tier.
+    pub resolution_seconds: u64,
+    /// The minimum number of seconds to keep entries in the tier for.
+    pub retention_seconds: u64,
+}
+
+impl HistoryTierConfig {
+    /// Gets the directory the tier is persisted to.
+    ///
+    /// # Arguments
+    /// * `base_dir` - The directory the base stats history is persisted to.
+    pub fn dir(&self, base_dir: &Path) -> PathBuf {
+        base_dir.join(format!("tier_{}s", self.resolution_seconds))
+    }
+
+    /// Gets the amount of time of stats consolidated into each entry in the tier.
+    pub fn resolution(&self) -> Duration {
+        Duration::from_secs(self.resolution_seconds)
+    }
+
+    /// Gets the bucket that stats collected at the provided time belong to. Stats in the same bucket are consolidated into the same entry.
+    fn bucket(&self, time: DateTime<Local>) -> i64 {
+        time.timestamp().div_euclid(self.resolution_seconds as i64)
+    }
+}
+
+/// Sorts the provided tier configurations from highest to lowest resolution, and leaves out any that don't have a lower resolution than the tier below them.
+///
+/// # Arguments
+/// * `configs` - The tier configurations.
+/// * `base_resolution` - How often entries are added to the base stats history.
+pub fn validate_history_tiers(
+    mut configs: Vec<HistoryTierConfig>,
+    base_resolution: Duration,
+) -> Vec<HistoryTierConfig> {
+    configs.sort_by_key(|x| x.resolution_seconds);
+    let mut tiers = Vec::new();
+    let mut previous_resolution = base_resolution;
+    for config in configs {
+        if config.resolution() <= previous_resolution {
+            println!(
+                "Ignoring history tier with a resolution of {} seconds, since it isn't lower than the resolution of the tier below it",
+                config.resolution_seconds
+            );
+            continue;
+        }
+
+        previous_resolution = config.resolution();
+        tiers.push(config);
+    }
+
+    tiers
+}
+
+/// The tiers of persisted stats history below the base history. As entries are added to the base history, they're consolidated into each tier in turn.
+pub struct HistoryTiers {
+    /// The tiers, from highest to lowest resolution.
+    tiers: Vec<HistoryTier>,
+}
+
+/// A tier of persisted stats history.
+struct HistoryTier {
+    /// The configuration for the tier.
+    config: HistoryTierConfig,
+    /// The directory the tier is persisted to.
+    dir: PathBuf,
+    /// The entries from the tier below that haven't been consolidated into this tier yet, from oldest to newest. They're all in the same bucket, unless stats weren't being collected for a while.
+    pending: Vec<AllStats>,
+}
+
+impl HistoryTiers {
+    /// Loads each of the provided tiers. Entries in the tier below each tier that haven't been consolidated into it yet, like ones added before the tier was configured, are consolidated into it now.
+    ///
+    /// # Arguments
+    /// * `base_dir` - The directory the base stats history is persisted to.
+    /// * `configs` - The configuration for each tier, from highest to lowest resolution.
+    /// * `collectors` - The collectors to consolidate stats with.
+    pub fn load(
+        base_dir: &Path,
+        configs: &[HistoryTierConfig],
+        collectors: &CollectorRegistry,
+    ) -> HistoryTiers {
+        let mut tiers = Vec::new();
+        let mut lower_dir = base_dir.to_path_buf();
+        for config in configs {
+            let mut tier = HistoryTier {
+                config: config.clone(),
+                dir: config.dir(base_dir),
+                pending: Vec::new(),
+            };
+            if let Err(e) = tier.catch_up(&lower_dir, collectors) {
+                println!(
+                    "Error loading persisted stats for history tier {:?}: {}",
+                    tier.dir, e
+                );
+            }
+
+            lower_dir = tier.dir.clone();
+            tiers.push(tier);
+        }
+
+        HistoryTiers { tiers }
+    }
+
+    /// Adds a new entry from the base history. Once an entry is added for a later bucket in a tier, the entries pending for that tier are consolidated and added to it, which is then added to the tier above it, and so on.
+    ///
+    /// # Arguments
+    /// * `stats` - The entry added to the base history.
+    /// * `collectors` - The collectors to consolidate stats with.
+    pub fn add(&mut self, stats: AllStats, collectors: &CollectorRegistry) {
+        let mut new_stats = stats;
+        for tier in &mut self.tiers {
+            new_stats = match tier.add(new_stats, collectors) {
+                Some(x) => x,
+                None => break,
+            };
+        }
+    }
+}
+
+impl HistoryTier {
+    /// Adds the entries persisted in the tier below that are newer than the newest entry in this tier.
+    fn catch_up(&mut self, lower_dir: &Path, collectors: &CollectorRegistry) -> io::Result<()> {
+        let lower_stats = match persisted_stats_time_range(&self.dir)? {
+            Some((_, newest)) => load_persisted_stats_after(lower_dir, newest)?,
+            None => load_persisted_stats(lower_dir)?,
+        };
+        for stats in lower_stats {
+            self.add(stats, collectors);
+        }
+
+        Ok(())
+    }
+
+    /// Adds an entry from the tier below. If it's in a later bucket than the pending entries, they're consolidated into a new entry in this tier, which is persisted and returned.
+    fn add(&mut self, stats: AllStats, collectors: &CollectorRegistry) -> Option<AllStats> {
+        let consolidated = match self.pending.last() {
+            Some(last)
+                if self.config.bucket(last.collection_time)
+                    != self.config.bucket(stats.collection_time) =>
+            {
+                let consolidated = collectors.consolidate(&self.pending);
+                self.pending.clear();
+                if let Err(e) = self.persist(&consolidated) {
+                    println!("Error persisting stats to {:?}: {}", self.dir, e);
+                }
+                Some(consolidated)
+            }
+            _ => None,
+        };
+
+        self.pending.push(stats);
+        consolidated
+    }
+
+    /// Persists an entry in this tier. Once the current history file covers the retention period, it replaces the old one, so the entries in the retention period are always kept.
+    fn persist(&self, stats: &AllStats) -> io::Result<()> {
+        if let Some(start_time) = current_persisted_stats_start_time(&self.dir)? {
+            let covered = stats.collection_time - start_time;
+            if covered >= chrono::Duration::seconds(self.config.retention_seconds as i64) {
+                rotate_persisted_stats(&self.dir)?;
+            }
+        }
+
+        append_persisted_stats(stats, &self.dir)
+    }
+}
+
+/// Loads the persisted stats history from the tier that best fits the provided query. That's the highest resolution tier that goes back to the start of the query (or its end, if it doesn't have a start). If none of them go back that far, the tier that goes back the furthest is used. Queries without a start or end use the base history.
+///
+/// Returns the loaded history, along with the tier it was loaded from, or `None` if it's the base history.
+///
+/// # Arguments
+/// * `base_dir` - The directory the base stats history is persisted to.
+/// * `tiers` - The configuration for each tier, from highest to lowest resolution.
+/// * `query` - The query to load the history for.
+pub fn load_history_for<'a>(
+    base_dir: &Path,
+    tiers: &'a [HistoryTierConfig],
+    query: &HistoryQuery,
+) -> io::Result<(StatsHistory, Option<&'a HistoryTierConfig>)> {
+    let tier = match query.from.or(query.to) {
+        Some(start) => select_history_tier(base_dir, tiers, start)?,
+        None => None,
+    };
+    let dir = match tier {
+        Some(x) => x.dir(base_dir),
+        None => base_dir.to_path_buf(),
+    };
+
+    Ok((StatsHistory::load_from(&dir)?, tier))
+}
+
+/// Finds the highest resolution tier with persisted stats going back to `start`, or the one that goes back the furthest if there aren't any. `None` is the base history.
+fn select_history_tier<'a>(
+    base_dir: &Path,
+    tiers: &'a [HistoryTierConfig],
+    start: DateTime<Local>,
+) -> io::Result<Option<&'a HistoryTierConfig>> {
+    let mut furthest: Option<(Option<&HistoryTierConfig>, DateTime<Local>)> = None;
+    for tier in std::iter::once(None).chain(tiers.iter().map(Some)) {
+        let dir = match tier {
+            Some(x) => x.dir(base_dir),
+            None => base_dir.to_path_buf(),
+        };
+        let oldest = match persisted_stats_time_range(&dir)? {
+            Some((oldest, _)) => oldest,
+            None => continue,
+        };
+
+        if oldest <= start {
+            return Ok(tier);
+        }
+        if furthest.map(|(_, x)| oldest < x).unwrap_or(true) {
+            furthest = Some((tier, oldest));
+        }
+    }
+
+    Ok(furthest.and_then(|(tier, _)| tier))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::collectors::CollectorConfig;
+    use crate::filters::StatsFilters;
+    use crate::stats::CpuStats;
+
+    /// A time that's at the start of a bucket for all the resolutions used in these tests.
+    const START_TIMESTAMP: i64 = 1_600_000_200;
+
+    /// Builds a registry with only the CPU collector enabled, so consolidation is quick and predictable.
+    fn cpu_only_collectors() -> CollectorRegistry {
+        let configs: BTreeMap<String, CollectorConfig> = [
+            "general",
+            "memory",
+            "filesystems",
+            "disks",
+            "sensors",
+            "network",
+            "processes",
+            "cgroups",
+        ]
+        .iter()
+        .map(|x| (x.to_string(), CollectorConfig::Enabled(false)))
+        .collect();
+        CollectorRegistry::new(
+            &configs,
+            Duration::from_secs(3600),
+            Duration::from_secs(1),
+            &StatsFilters::default(),
+            false,
+        )
+    }
+
+    fn time(seconds: i64) -> DateTime<Local> {
+        Local.timestamp(START_TIMESTAMP + seconds, 0)
+    }
+
+    fn stats(seconds: i64, load_percent: f32) -> AllStats {
+        let mut stats = AllStats::empty(time(seconds));
+        stats.cpu = Some(CpuStats {
+            aggregate_load_percent: Some(load_percent),
+            ..CpuStats::default()
+        });
+        stats
+    }
+
+    fn tier_config(resolution_seconds: u64, retention_seconds: u64) -> HistoryTierConfig {
+        HistoryTierConfig {
+            resolution_seconds,
+            retention_seconds,
+        }
+    }
+
+    fn load_percent(stats: &AllStats) -> Option<f32> {
+        stats.cpu.as_ref().and_then(|x| x.aggregate_load_percent)
+    }
+
+    fn collection_times(stats: &[AllStats]) -> Vec<DateTime<Local>> {
+        stats.iter().map(|x| x.collection_time).collect()
+    }
+
+    #[test]
+    fn add_consolidates_pending_entries_once_a_later_bucket_starts() {
+        let dir = tempfile::tempdir().unwrap();
+        let collectors = cpu_only_collectors();
+        let configs = [tier_config(60, 3600), tier_config(120, 3600)];
+        let mut tiers = HistoryTiers::load(dir.path(), &configs, &collectors);
+
+        for (seconds, load) in [(0, 10.0), (20, 20.0), (40, 60.0)] {
+            tiers.add(stats(seconds, load), &collectors);
+        }
+        assert!(load_persisted_stats(&configs[0].dir(dir.path()))
+            .unwrap()
+            .is_empty());
+
+        tiers.add(stats(60, 80.0), &collectors);
+        let persisted = load_persisted_stats(&configs[0].dir(dir.path())).unwrap();
+        assert_eq!(vec![time(40)], collection_times(&persisted));
+        assert_eq!(Some(30.0), load_percent(&persisted[0]));
+        let consolidated = persisted[0].consolidated.as_ref().unwrap();
+        assert_eq!(Some(10.0), load_percent(&consolidated.min));
+        assert_eq!(Some(60.0), load_percent(&consolidated.max));
+
+        // the second entry in the first tier is in the same bucket of the second tier, so it isn't consolidated into it yet
+        tiers.add(stats(120, 40.0), &collectors);
+        assert_eq!(
+            vec![time(40), time(60)],
+            collection_times(&load_persisted_stats(&configs[0].dir(dir.path())).unwrap())
+        );
+        assert!(load_persisted_stats(&configs[1].dir(dir.path()))
+            .unwrap()
+            .is_empty());
+
+        tiers.add(stats(180, 40.0), &collectors);
+        let persisted = load_persisted_stats(&configs[1].dir(dir.path())).unwrap();
+        assert_eq!(vec![time(60)], collection_times(&persisted));
+        assert_eq!(Some(55.0), load_percent(&persisted[0]));
+        let consolidated = persisted[0].consolidated.as_ref().unwrap();
+        assert_eq!(Some(10.0), load_percent(&consolidated.min));
+        assert_eq!(Some(80.0), load_percent(&consolidated.max));
+    }
+
+    #[test]
+    fn catch_up_does_not_add_entries_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let collectors = cpu_only_collectors();
+        let configs = [tier_config(60, 3600)];
+        let tier_dir = configs[0].dir(dir.path());
+        for seconds in (0..=180).step_by(20) {
+            append_persisted_stats(&stats(seconds, 50.0), dir.path()).unwrap();
+        }
+
+        // the entries in the last bucket stay pending until an entry in a later bucket is added
+        HistoryTiers::load(dir.path(), &configs, &collectors);
+        let expected = vec![time(40), time(100), time(160)];
+        assert_eq!(
+            expected,
+            collection_times(&load_persisted_stats(&tier_dir).unwrap())
+        );
+
+        HistoryTiers::load(dir.path(), &configs, &collectors);
+        assert_eq!(
+            expected,
+            collection_times(&load_persisted_stats(&tier_dir).unwrap())
+        );
+
+        append_persisted_stats(&stats(240, 50.0), dir.path()).unwrap();
+        HistoryTiers::load(dir.path(), &configs, &collectors);
+        assert_eq!(
+            vec![time(40), time(100), time(160), time(180)],
+            collection_times(&load_persisted_stats(&tier_dir).unwrap())
+        );
+    }
+
+    #[test]
+    fn persist_rotates_once_the_current_file_covers_the_retention_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let collectors = cpu_only_collectors();
+        let configs = [tier_config(60, 180)];
+        let tier_dir = configs[0].dir(dir.path());
+        let mut tiers = HistoryTiers::load(dir.path(), &configs, &collectors);
+
+        for seconds in (0..=360).step_by(60) {
+            tiers.add(stats(seconds, 50.0), &collectors);
+        }
+        assert_eq!(
+            Some((time(0), time(300))),
+            persisted_stats_time_range(&tier_dir).unwrap()
+        );
+        assert_eq!(
+            Some(time(180)),
+            current_persisted_stats_start_time(&tier_dir).unwrap()
+        );
+
+        // the old file is replaced, but the entries in the retention period are kept
+        for seconds in (420..=600).step_by(60) {
+            tiers.add(stats(seconds, 50.0), &collectors);
+        }
+        assert_eq!(
+            vec![time(360), time(420), time(480), time(540)],
+            collection_times(&load_persisted_stats(&tier_dir).unwrap())
+        );
+        assert_eq!(
+            Some(time(540)),
+            current_persisted_stats_start_time(&tier_dir).unwrap()
+        );
+    }
+
+    #[test]
+    fn select_history_tier_prefers_highest_resolution_that_goes_back_far_enough() {
+        let dir = tempfile::tempdir().unwrap();
+        let configs = [tier_config(60, 3600), tier_config(300, 86400)];
+        append_persisted_stats(&stats(1000, 50.0), dir.path()).unwrap();
+        append_persisted_stats(&stats(500, 50.0), &configs[0].dir(dir.path())).unwrap();
+        append_persisted_stats(&stats(0, 50.0), &configs[1].dir(dir.path())).unwrap();
+
+        let selected = |seconds| {
+            select_history_tier(dir.path(), &configs, time(seconds))
+                .unwrap()
+                .map(|x| x.resolution_seconds)
+        };
+        assert_eq!(None, selected(1200));
+        assert_eq!(Some(60), selected(600));
+        assert_eq!(Some(300), selected(100));
+    }
+
+    #[test]
+    fn select_history_tier_falls_back_to_the_tier_going_back_furthest() {
+        let dir = tempfile::tempdir().unwrap();
+        let configs = [tier_config(60, 3600), tier_config(300, 86400)];
+        append_persisted_stats(&stats(1000, 50.0), dir.path()).unwrap();
+        append_persisted_stats(&stats(0, 50.0), &configs[0].dir(dir.path())).unwrap();
+        append_persisted_stats(&stats(500, 50.0), &configs[1].dir(dir.path())).unwrap();
+
+        let selected = select_history_tier(dir.path(), &configs, time(-1000)).unwrap();
+        assert_eq!(Some(60), selected.map(|x| x.resolution_seconds));
+
+        // tiers without any persisted stats are skipped
+        let empty_dir = tempfile::tempdir().unwrap();
+        append_persisted_stats(&stats(1000, 50.0), empty_dir.path()).unwrap();
+        let selected = select_history_tier(empty_dir.path(), &configs, time(-1000)).unwrap();
+        assert!(selected.is_none());
+    }
+}
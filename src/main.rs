@@ -1,12 +1,19 @@
 //! Provides a simple dashboard for viewing system stats, and an API for retrieving said stats programmatically.
 
-use std::num::NonZeroUsize;
+use std::{collections::BTreeMap, num::NonZeroUsize, sync::Arc};
 
+use chrono::{DateTime, Local};
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json;
-use rocket::{figment::Figment, http::Status, Rocket, State};
+use rocket::tokio::{select, sync::broadcast::error::RecvError, task::spawn_blocking};
+use rocket::{
+    figment::Figment,
+    http::{ContentType, Status},
+    Rocket, Shutdown, State,
+};
 use rocket_dyn_templates::Template;
 use serde::Deserialize;
-use systemstat::{Duration, Platform, System};
+use systemstat::Duration;
 
 mod stats;
 use stats::*;
@@ -20,11 +27,51 @@ use dashboard_context::*;
 mod error_context;
 use error_context::*;
 
+mod api_error;
+use api_error::*;
+
+mod metrics;
+use metrics::*;
+
+mod alerts;
+use alerts::*;
+
+mod email_notifier;
+use email_notifier::*;
+
+mod webhook_notifier;
+use webhook_notifier::*;
+
+mod processes;
+use processes::*;
+
+mod sensors;
+
+mod cgroups;
+use cgroups::*;
+
+mod filters;
+use filters::*;
+
+mod collectors;
+use collectors::*;
+
+mod snapshot_response;
+use snapshot_response::*;
+
+mod consolidation;
+use consolidation::*;
+
+mod history_tiers;
+use history_tiers::*;
+
 #[macro_use]
 extern crate rocket;
 
-const CPU_LOAD_SAMPLE_DURATION: Duration = Duration::from_millis(500);
 const DEFAULT_DARK_MODE: bool = true;
+const MAX_GAP_CONSOLIDATION_INTERVALS: u32 = 2;
+const DEFAULT_PROCESS_SORT_ORDER: ProcessSortOrder = ProcessSortOrder::Cpu;
+const DEFAULT_CGROUP_SORT_ORDER: CgroupSortOrder = CgroupSortOrder::Cpu;
 
 const RECENT_HISTORY_SIZE_CONFIG_KEY: &str = "recent_history_size";
 const DEFAULT_RECENT_HISTORY_SIZE: usize = 180;
@@ -42,103 +89,376 @@ const HISTORY_FILES_DIRECTORY_CONFIG_KEY: &str = "history_files_directory";
 const DEFAULT_HISTORY_FILES_DIRECTORY: &str = "./stats_history";
 
 const HISTORY_FILES_DIRECTORY_MAX_SIZE_CONFIG_KEY: &str = "history_files_max_size_bytes";
-const DEFAULT_HISTORY_FILES_DIRECTORY_MAX_SIZE_BYTES: u64 = 2_000_000;
+const DEFAULT_HISTORY_FILES_DIRECTORY_MAX_SIZE_BYTES: u64 = 40_000_000;
+
+const HISTORY_TIERS_CONFIG_KEY: &str = "history_tiers";
+
+const ALERT_RULES_CONFIG_KEY: &str = "alert_rules";
+
+const EMAIL_NOTIFICATIONS_CONFIG_KEY: &str = "email_notifications";
+
+const WEBHOOK_NOTIFICATIONS_CONFIG_KEY: &str = "webhook_notifications";
+
+const MOUNT_FILTER_CONFIG_KEY: &str = "filesystem_filter";
+
+const INTERFACE_FILTER_CONFIG_KEY: &str = "network_interface_filter";
+
+const COLLECTORS_CONFIG_KEY: &str = "collectors";
+
+const MIN_FRESH_STATS_INTERVAL_CONFIG_KEY: &str = "min_fresh_stats_interval_seconds";
+const DEFAULT_MIN_FRESH_STATS_INTERVAL_SECONDS: u64 = 1;
+
+const CONSOLIDATION_CONFIG_KEY: &str = "consolidation";
+
+const TEMPLATE_DIRECTORY_CONFIG_KEY: &str = "template_dir";
+const DEFAULT_TEMPLATE_DIRECTORY: &str = "templates";
 
 /// Endpoint to get all the system stats.
-#[get("/stats")]
-fn get_all_stats(stats_history: &State<UpdatingStatsHistory>) -> Result<Json<AllStats>, Status> {
-    match stats_history
-        .stats_history
-        .lock()
-        .unwrap()
-        .get_most_recent_stats()
-    {
-        Some(x) => Ok(Json((*x).clone())),
-        None => Err(Status::InternalServerError),
-    }
+#[get("/stats?<fresh>")]
+async fn get_all_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<AllStats>, Status> {
+    snapshot_response(stats_history, fresh, Some).await
 }
 
 /// Endpoint to get general stats.
-#[get("/stats/general")]
-fn get_general_stats(
+#[get("/stats/general?<fresh>")]
+async fn get_general_stats(
     stats_history: &State<UpdatingStatsHistory>,
-) -> Result<Json<GeneralStats>, Status> {
-    match stats_history
-        .stats_history
-        .lock()
-        .unwrap()
-        .get_most_recent_stats()
-    {
-        Some(x) => Ok(Json((*x).general.clone())),
-        None => Err(Status::InternalServerError),
-    }
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<GeneralStats>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.general).await
 }
 
 /// Endpoint to get CPU stats.
-#[get("/stats/cpu")]
-fn get_cpu_stats(stats_history: &State<UpdatingStatsHistory>) -> Result<Json<CpuStats>, Status> {
-    match stats_history
-        .stats_history
-        .lock()
-        .unwrap()
-        .get_most_recent_stats()
-    {
-        Some(x) => Ok(Json((*x).cpu.clone())),
-        None => Err(Status::InternalServerError),
-    }
+#[get("/stats/cpu?<fresh>")]
+async fn get_cpu_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<CpuStats>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.cpu).await
+}
+
+/// Endpoint to get disk I/O stats.
+#[get("/stats/disks?<fresh>")]
+async fn get_disk_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<Vec<DiskStats>>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.disks).await
 }
 
 /// Endpoint to get memory stats.
-#[get("/stats/memory")]
-fn get_memory_stats() -> Result<Json<MemoryStats>, Status> {
-    match MemoryStats::from(&System::new()) {
-        Some(x) => Ok(Json(x)),
-        None => Err(Status::InternalServerError),
-    }
+#[get("/stats/memory?<fresh>")]
+async fn get_memory_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<MemoryStats>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.memory).await
 }
 
 /// Endpoint to get filesystem stats.
-#[get("/stats/filesystems")]
-fn get_filesystem_stats() -> Result<Json<Vec<MountStats>>, Status> {
-    match MountStats::from(&System::new()) {
-        Some(x) => Ok(Json(x)),
+#[get("/stats/filesystems?<fresh>")]
+async fn get_filesystem_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<Vec<MountStats>>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.filesystems).await
+}
+
+/// Endpoint to get network stats.
+#[get("/stats/network?<fresh>")]
+async fn get_network_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    fresh: Option<bool>,
+) -> Result<SnapshotResponse<NetworkStats>, Status> {
+    snapshot_response(stats_history, fresh, |x| x.network).await
+}
+
+/// Endpoint to get stats for each process.
+#[get("/stats/processes?<sort>&<limit>")]
+fn get_process_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    sort: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<ProcessStats>>, ApiErrorResponse> {
+    let sort_order = match sort {
+        Some(x) => match ProcessSortOrder::parse(x) {
+            Some(order) => order,
+            None => {
+                return Err(ApiError::response(
+                    Status::BadRequest,
+                    format!(
+                        "sort must be one of cpu, memory, threads, pid, or name, not {}",
+                        x
+                    ),
+                ))
+            }
+        },
+        None => DEFAULT_PROCESS_SORT_ORDER,
+    };
+
+    let mut processes = match stats_history.collectors.latest().processes {
+        Some(x) => x.to_vec(),
+        None => {
+            return Err(ApiError::response(
+                Status::NotFound,
+                "Process stats aren't available.".to_string(),
+            ))
+        }
+    };
+    sort_order.sort(&mut processes);
+    if let Some(limit) = limit {
+        processes.truncate(limit);
+    }
+
+    Ok(Json(processes))
+}
+
+/// Endpoint to get stats for each cgroup.
+#[get("/stats/cgroups?<sort>&<limit>")]
+fn get_cgroup_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    sort: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<CgroupStats>>, ApiErrorResponse> {
+    let sort_order = match sort {
+        Some(x) => match CgroupSortOrder::parse(x) {
+            Some(order) => order,
+            None => {
+                return Err(ApiError::response(
+                    Status::BadRequest,
+                    format!(
+                        "sort must be one of cpu, memory, io, pids, or name, not {}",
+                        x
+                    ),
+                ))
+            }
+        },
+        None => DEFAULT_CGROUP_SORT_ORDER,
+    };
+
+    let mut cgroups = match stats_history.collectors.latest().cgroups {
+        Some(x) => x.to_vec(),
+        None => {
+            return Err(ApiError::response(
+                Status::NotFound,
+                "cgroup v2 stats aren't available on this system.".to_string(),
+            ))
+        }
+    };
+    sort_order.sort(&mut cgroups);
+    if let Some(limit) = limit {
+        cgroups.truncate(limit);
+    }
+
+    Ok(Json(cgroups))
+}
+
+/// Endpoint to get recent stats.
+#[get("/stats/recent?<from>&<to>&<limit>")]
+fn get_recent_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<AllStats>>, ApiErrorResponse> {
+    let query = build_history_query(from, to, limit)?;
+    Ok(Json(
+        stats_history.stats_history.lock().unwrap().query(&query),
+    ))
+}
+
+/// Endpoint to get persisted stats.
+#[get("/stats/history?<from>&<to>&<limit>")]
+fn get_persisted_stats(
+    history_persistence_config: &State<HistoryPersistenceConfig>,
+    filters: &State<StatsFilters>,
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<AllStats>>, ApiErrorResponse> {
+    let query = build_history_query(from, to, limit)?;
+    match history_persistence_config.inner() {
+        HistoryPersistenceConfig::Enabled { dir, tiers, .. } => {
+            match load_history_for(dir, tiers, &query) {
+                Ok((mut history, _)) => {
+                    history.apply_filters(filters);
+                    Ok(Json(history.query(&query)))
+                }
+                Err(e) => {
+                    println!("Error loading persisted stats from {:?}: {}", dir, e);
+                    Err(ApiError::response(
+                        Status::InternalServerError,
+                        "Error loading persisted stats.".to_string(),
+                    ))
+                }
+            }
+        }
+        HistoryPersistenceConfig::Disabled => Err(ApiError::response(
+            Status::NotFound,
+            "Stats history persistence is disabled.".to_string(),
+        )),
+    }
+}
+
+/// Endpoint to stream stats as they're collected. Newly collected stats are sent as `stats` events, and consolidated stats are sent as `consolidated` events.
+#[get("/stats/stream")]
+fn stream_stats(
+    stats_history: &State<UpdatingStatsHistory>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let mut updates = stats_history.subscribe();
+    EventStream! {
+        loop {
+            let update = select! {
+                update = updates.recv() => match update {
+                    Ok(x) => x,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            yield match update {
+                StatsHistoryUpdate::Collected { stats, .. } => Event::json(&stats).event("stats"),
+                StatsHistoryUpdate::Consolidated { stats, .. } => Event::json(&stats).event("consolidated"),
+            };
+        }
+    }
+}
+
+/// Endpoint to stream updates for the charts in the dashboard as stats are collected. Updates for newly collected stats are sent as `stats` events, and updates for consolidated stats are sent as `consolidated` events, using the consolidated values configured for the charts.
+#[get("/dashboard/stream")]
+fn stream_dashboard(
+    stats_history: &State<UpdatingStatsHistory>,
+    dashboard_config: &State<DashboardConfig>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let mut updates = stats_history.subscribe();
+    let collectors = Arc::clone(&stats_history.collectors);
+    let chart_consolidation = dashboard_config.chart_consolidation;
+    EventStream! {
+        loop {
+            let update = select! {
+                update = updates.recv() => match update {
+                    Ok(x) => x,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            yield match update {
+                StatsHistoryUpdate::Collected { stats, new_entry } => {
+                    Event::json(&DashboardChartsUpdate::from_stats(&stats, new_entry, &collectors)).event("stats")
+                }
+                StatsHistoryUpdate::Consolidated { stats, new_entry } => {
+                    let stats = stats.consolidated_with(chart_consolidation);
+                    Event::json(&DashboardChartsUpdate::from_stats(stats, new_entry, &collectors)).event("consolidated")
+                }
+            };
+        }
+    }
+}
+
+/// Endpoint to get all the system stats in the Prometheus text format.
+#[get("/metrics")]
+fn get_metrics(
+    stats_history: &State<UpdatingStatsHistory>,
+) -> Result<(ContentType, String), Status> {
+    match stats_history
+        .stats_history
+        .lock()
+        .unwrap()
+        .get_most_recent_stats()
+    {
+        Some(x) => Ok((
+            ContentType::with_params("text", "plain", ("version", "0.0.4")),
+            to_prometheus_text(x),
+        )),
         None => Err(Status::InternalServerError),
     }
 }
 
-/// Endpoint to get network stats.
-#[get("/stats/network")]
-fn get_network_stats() -> Json<NetworkStats> {
-    Json(NetworkStats::from(&System::new()))
+/// Endpoint to get the status of each alert.
+#[get("/alerts")]
+fn get_alerts(stats_history: &State<UpdatingStatsHistory>) -> Json<Vec<AlertStatus>> {
+    Json(stats_history.alerts.lock().unwrap().statuses().to_vec())
 }
 
 /// Endpoint to view the dashboard.
 #[get("/dashboard?<dark>")]
-fn dashboard(stats_history: &State<UpdatingStatsHistory>, dark: Option<bool>) -> Template {
+fn dashboard(
+    stats_history: &State<UpdatingStatsHistory>,
+    dashboard_config: &State<DashboardConfig>,
+    dark: Option<bool>,
+) -> Template {
     let context = DashboardContext::from_history(
         &stats_history.stats_history.lock().unwrap(),
+        &stats_history.collectors,
+        dashboard_config,
+        stats_history.alerts.lock().unwrap().statuses(),
+        true,
         dark.unwrap_or(DEFAULT_DARK_MODE),
     );
     Template::render("dashboard", &context)
 }
 
 /// Endpoint to view a dashboard of persisted stats.
-#[get("/dashboard/history?<dark>")]
+#[get("/dashboard/history?<dark>&<from>&<to>")]
 fn history_dashboard(
+    stats_history: &State<UpdatingStatsHistory>,
     history_persistence_config: &State<HistoryPersistenceConfig>,
+    dashboard_config: &State<DashboardConfig>,
+    filters: &State<StatsFilters>,
     dark: Option<bool>,
+    from: Option<&str>,
+    to: Option<&str>,
 ) -> Result<Template, Status> {
     match history_persistence_config.inner() {
-        HistoryPersistenceConfig::Enabled { dir, size_limit: _ } => {
-            let history = match StatsHistory::load_from(dir) {
+        HistoryPersistenceConfig::Enabled { dir, tiers, .. } => {
+            let query = match build_history_query(from, to, None) {
+                Ok(x) => x,
+                Err(e) => {
+                    return Ok(Template::render(
+                        "error",
+                        &ErrorContext {
+                            title: "Stats History".to_string(),
+                            message: e.1.into_inner().message,
+                        },
+                    ))
+                }
+            };
+            let (mut history, tier) = match load_history_for(dir, tiers, &query) {
                 Ok(x) => x,
                 Err(e) => {
                     println!("Error loading persisted stats from {:?}: {}", dir, e);
                     return Err(Status::InternalServerError);
                 }
             };
-            let context =
-                DashboardContext::from_history(&history, dark.unwrap_or(DEFAULT_DARK_MODE));
+            history.apply_filters(filters);
+            let history = StatsHistory::from_stats(history.query(&query));
+            // entries in lower resolution tiers are further apart, so gaps between them need to be longer too
+            let tier_dashboard_config = match tier {
+                Some(x) => DashboardConfig {
+                    max_gap: chrono::Duration::from_std(
+                        x.resolution() * MAX_GAP_CONSOLIDATION_INTERVALS,
+                    )
+                    .unwrap(),
+                    ..*dashboard_config.inner()
+                },
+                None => *dashboard_config.inner(),
+            };
+            let context = DashboardContext::from_history(
+                &history,
+                &stats_history.collectors,
+                &tier_dashboard_config,
+                stats_history.alerts.lock().unwrap().statuses(),
+                false,
+                dark.unwrap_or(DEFAULT_DARK_MODE),
+            );
             Ok(Template::render("dashboard", &context))
         }
         HistoryPersistenceConfig::Disabled => Ok(Template::render(
@@ -162,8 +482,17 @@ fn rocket() -> Rocket<rocket::Build> {
                 get_cpu_stats,
                 get_memory_stats,
                 get_filesystem_stats,
+                get_disk_stats,
                 get_network_stats,
+                get_process_stats,
+                get_cgroup_stats,
+                get_recent_stats,
+                get_persisted_stats,
+                stream_stats,
+                get_metrics,
+                get_alerts,
                 dashboard,
+                stream_dashboard,
                 history_dashboard,
             ],
         )
@@ -205,28 +534,211 @@ fn rocket() -> Rocket<rocket::Build> {
             HISTORY_FILES_DIRECTORY_MAX_SIZE_CONFIG_KEY,
             DEFAULT_HISTORY_FILES_DIRECTORY_MAX_SIZE_BYTES,
         );
+        let history_tiers: Vec<HistoryTierConfig> =
+            get_config_value(config, HISTORY_TIERS_CONFIG_KEY, Vec::new());
         HistoryPersistenceConfig::Enabled {
             dir: history_files_dir.into(),
             size_limit: history_files_dir_max_size,
+            tiers: validate_history_tiers(
+                history_tiers,
+                Duration::from_secs(update_frequency_secs * consolidation_limit as u64),
+            ),
         }
     } else {
         HistoryPersistenceConfig::Disabled
     };
 
+    let alert_rules: Vec<AlertRule> = get_config_value(config, ALERT_RULES_CONFIG_KEY, Vec::new());
+    let mut alert_notifiers: Vec<Box<dyn AlertNotifier>> = Vec::new();
+    let email_config: Option<EmailConfig> =
+        get_config_value(config, EMAIL_NOTIFICATIONS_CONFIG_KEY, None);
+    if let Some(email_config) = email_config {
+        match EmailNotifier::new(email_config) {
+            Ok(x) => alert_notifiers.push(Box::new(x)),
+            Err(e) => println!("Error setting up email notifications: {}", e),
+        }
+    }
+    let webhook_config: Option<WebhookConfig> =
+        get_config_value(config, WEBHOOK_NOTIFICATIONS_CONFIG_KEY, None);
+    if let Some(webhook_config) = webhook_config {
+        let template_dir = get_config_value(
+            config,
+            TEMPLATE_DIRECTORY_CONFIG_KEY,
+            DEFAULT_TEMPLATE_DIRECTORY.to_string(),
+        );
+        match WebhookNotifier::new(webhook_config, template_dir.as_ref()) {
+            Ok(x) => alert_notifiers.push(Box::new(x)),
+            Err(e) => println!("Error setting up webhook notifications: {}", e),
+        }
+    }
+
+    let mount_filter_config: MountFilterConfig = get_config_value(
+        config,
+        MOUNT_FILTER_CONFIG_KEY,
+        MountFilterConfig::default(),
+    );
+    let mount_filter = match MountFilter::new(mount_filter_config) {
+        Ok(x) => x,
+        Err(e) => {
+            println!(
+                "Error setting up filesystem filter, so all filesystems will be included: {}",
+                e
+            );
+            MountFilter::default()
+        }
+    };
+    let interface_filter_config: InterfaceFilterConfig = get_config_value(
+        config,
+        INTERFACE_FILTER_CONFIG_KEY,
+        InterfaceFilterConfig::default(),
+    );
+    let interface_filter = match InterfaceFilter::new(interface_filter_config) {
+        Ok(x) => x,
+        Err(e) => {
+            println!(
+                "Error setting up network interface filter, so all interfaces will be included: {}",
+                e
+            );
+            InterfaceFilter::default()
+        }
+    };
+    let filters = StatsFilters {
+        mounts: mount_filter,
+        interfaces: interface_filter,
+    };
+
+    let collector_configs: BTreeMap<String, CollectorConfig> =
+        get_config_value(config, COLLECTORS_CONFIG_KEY, BTreeMap::new());
+    let min_fresh_stats_interval_secs = get_config_value(
+        config,
+        MIN_FRESH_STATS_INTERVAL_CONFIG_KEY,
+        DEFAULT_MIN_FRESH_STATS_INTERVAL_SECONDS,
+    );
+    let consolidation_config: ConsolidationConfig = get_config_value(
+        config,
+        CONSOLIDATION_CONFIG_KEY,
+        ConsolidationConfig::default(),
+    );
+    if consolidation_config.chart == ConsolidationStrategy::P95 && !consolidation_config.p95 {
+        println!(
+            "The 95th percentile is charted but isn't enabled, so averages will be charted instead"
+        );
+    }
+    let collectors = CollectorRegistry::new(
+        &collector_configs,
+        Duration::from_secs(update_frequency_secs),
+        Duration::from_secs(min_fresh_stats_interval_secs),
+        &filters,
+        consolidation_config.p95,
+    );
+
+    // consolidated entries are expected once per consolidation interval, so anything much longer means stats weren't being collected
+    let consolidation_interval =
+        Duration::from_secs(update_frequency_secs * consolidation_limit as u64);
+    let dashboard_config = DashboardConfig {
+        max_gap: chrono::Duration::from_std(
+            consolidation_interval * MAX_GAP_CONSOLIDATION_INTERVALS,
+        )
+        .unwrap(),
+        chart_consolidation: consolidation_config.chart,
+    };
+
     rocket = rocket
         .manage(persistence_config.clone())
+        .manage(dashboard_config)
+        .manage(filters.clone())
         .manage(UpdatingStatsHistory::new(
-            System::new(),
-            CPU_LOAD_SAMPLE_DURATION,
             Duration::from_secs(update_frequency_secs),
             NonZeroUsize::new(recent_history_size).unwrap(),
             NonZeroUsize::new(consolidation_limit).unwrap(),
             persistence_config,
+            Alerts::new(alert_rules, alert_notifiers),
+            collectors,
+            &filters,
         ));
 
     rocket
 }
 
+/// Builds a response with part of the most recent stats in the history, or of freshly collected stats if `fresh` is true. Responds with an error if there are no stats yet, or with a 404 if `get_stats` doesn't find the part of them that was asked for, like when its collector is disabled.
+async fn snapshot_response<T>(
+    stats_history: &UpdatingStatsHistory,
+    fresh: Option<bool>,
+    get_stats: fn(AllStats) -> Option<T>,
+) -> Result<SnapshotResponse<T>, Status> {
+    let stats = if fresh.unwrap_or(false) {
+        // collecting blocks until the collectors finish, which shouldn't hold up the async workers
+        let collectors = Arc::clone(&stats_history.collectors);
+        match spawn_blocking(move || collectors.collect_now()).await {
+            Ok(x) => x,
+            Err(_) => return Err(Status::InternalServerError),
+        }
+    } else {
+        match stats_history
+            .stats_history
+            .lock()
+            .unwrap()
+            .get_most_recent_stats()
+        {
+            Some(x) => x.clone(),
+            None => return Err(Status::InternalServerError),
+        }
+    };
+
+    let collection_time = stats.collection_time;
+    match get_stats(stats) {
+        Some(x) => Ok(SnapshotResponse::new(x, collection_time)),
+        None => Err(Status::NotFound),
+    }
+}
+
+/// Builds a history query from the provided query parameters. `from` and `to` must be RFC 3339 timestamps if present.
+fn build_history_query(
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<usize>,
+) -> Result<HistoryQuery, ApiErrorResponse> {
+    let from = parse_query_time("from", from)?;
+    let to = parse_query_time("to", to)?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ApiError::response(
+                Status::BadRequest,
+                "from must not be after to".to_string(),
+            ));
+        }
+    }
+
+    Ok(HistoryQuery { from, to, limit })
+}
+
+/// Parses a query parameter as an RFC 3339 timestamp. A space before the UTC offset is treated as a `+`, since that's what an unencoded `+` in a query string decodes to.
+fn parse_query_time(
+    name: &str,
+    value: Option<&str>,
+) -> Result<Option<DateTime<Local>>, ApiErrorResponse> {
+    match value {
+        Some(x) => match DateTime::parse_from_rfc3339(&restore_offset_sign(x)) {
+            Ok(time) => Ok(Some(time.with_timezone(&Local))),
+            Err(e) => Err(ApiError::response(
+                Status::BadRequest,
+                format!("{} must be an RFC 3339 timestamp: {}", name, e),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Replaces a space before a trailing `hh:mm` UTC offset with a `+`.
+fn restore_offset_sign(value: &str) -> String {
+    let offset_start = value.len().saturating_sub(6);
+    if value.len() > 6 && value.as_bytes()[offset_start] == b' ' {
+        format!("{}+{}", &value[..offset_start], &value[offset_start + 1..])
+    } else {
+        value.to_string()
+    }
+}
+
 /// Gets a value from the provided configuration, returning `default` if it's not found.
 fn get_config_value<'a, T>(config: &Figment, key: &str, default: T) -> T
 where
@@ -243,3 +755,37 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query_time_accepts_space_before_offset() {
+        let expected = DateTime::parse_from_rfc3339("2021-03-15T23:00:00+02:00").unwrap();
+        let parsed = parse_query_time("from", Some("2021-03-15T23:00:00 02:00"))
+            .ok()
+            .flatten();
+        assert_eq!(Some(expected.with_timezone(&Local)), parsed);
+    }
+
+    #[test]
+    fn parse_query_time_accepts_encoded_offsets() {
+        let expected = DateTime::parse_from_rfc3339("2021-03-15T21:00:00Z").unwrap();
+        for value in &[
+            "2021-03-15T21:00:00Z",
+            "2021-03-15T23:00:00+02:00",
+            "2021-03-15T19:00:00-02:00",
+        ] {
+            let parsed = parse_query_time("from", Some(value)).ok().flatten();
+            assert_eq!(Some(expected.with_timezone(&Local)), parsed);
+        }
+    }
+
+    #[test]
+    fn parse_query_time_rejects_invalid_times() {
+        assert!(parse_query_time("from", Some("2021-03-15")).is_err());
+        assert!(parse_query_time("from", Some("      ")).is_err());
+        assert!(matches!(parse_query_time("from", None), Ok(None)));
+    }
+}
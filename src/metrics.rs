@@ -0,0 +1,692 @@
+//! Exposition of stats in the Prometheus text format.
+
+use std::fmt::Write;
+
+use crate::stats::{
+    AllStats, DiskStats, MountStats, NetworkInterfaceStats, PressureValues, BYTES_PER_MB,
+};
+
+const METRIC_NAME_PREFIX: &str = "system_stats_";
+const BITS_PER_BYTE: f64 = 8.0;
+
+/// The type of a Prometheus metric.
+#[derive(Clone, Copy)]
+enum MetricType {
+    /// A value that can go up and down.
+    Gauge,
+    /// A value that only ever goes up, unless it's reset.
+    Counter,
+}
+
+impl MetricType {
+    fn name(self) -> &'static str {
+        match self {
+            MetricType::Gauge => "gauge",
+            MetricType::Counter => "counter",
+        }
+    }
+}
+
+/// A single value of a metric.
+struct Sample {
+    /// The labels identifying this sample, as (name, value) pairs.
+    labels: Vec<(&'static str, String)>,
+    /// The value of this sample.
+    value: f64,
+}
+
+impl Sample {
+    /// Builds a sample without any labels.
+    fn unlabeled(value: f64) -> Sample {
+        Sample {
+            labels: Vec::new(),
+            value,
+        }
+    }
+}
+
+/// Builds a Prometheus text format document.
+struct MetricsWriter {
+    output: String,
+}
+
+impl MetricsWriter {
+    fn new() -> MetricsWriter {
+        MetricsWriter {
+            output: String::new(),
+        }
+    }
+
+    /// Writes a metric and its samples. Nothing is written if there are no samples.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the metric, without the common prefix.
+    /// * `help` - A description of the metric.
+    /// * `metric_type` - The type of the metric.
+    /// * `samples` - The values of the metric.
+    fn write(&mut self, name: &str, help: &str, metric_type: MetricType, samples: Vec<Sample>) {
+        if samples.is_empty() {
+            return;
+        }
+
+        // writing to a String can't fail
+        writeln!(
+            self.output,
+            "# HELP {}{} {}",
+            METRIC_NAME_PREFIX, name, help
+        )
+        .unwrap();
+        writeln!(
+            self.output,
+            "# TYPE {}{} {}",
+            METRIC_NAME_PREFIX,
+            name,
+            metric_type.name()
+        )
+        .unwrap();
+        for sample in samples {
+            write!(self.output, "{}{}", METRIC_NAME_PREFIX, name).unwrap();
+            if !sample.labels.is_empty() {
+                let labels = sample
+                    .labels
+                    .iter()
+                    .map(|(label_name, value)| {
+                        format!("{}=\"{}\"", label_name, escape_label_value(value))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+                write!(self.output, "{{{}}}", labels).unwrap();
+            }
+            writeln!(self.output, " {}", sample.value).unwrap();
+        }
+    }
+
+    fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Renders the provided stats in the Prometheus text exposition format.
+pub fn to_prometheus_text(stats: &AllStats) -> String {
+    let mut writer = MetricsWriter::new();
+
+    if let Some(general) = &stats.general {
+        if let Some(x) = general.uptime_seconds {
+            writer.write(
+                "uptime_seconds",
+                "Number of seconds the system has been running.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x as f64)],
+            );
+        }
+        if let Some(x) = general.boot_timestamp {
+            writer.write(
+                "boot_time_seconds",
+                "Boot time in seconds since the UNIX epoch.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x as f64)],
+            );
+        }
+        if let Some(x) = &general.load_averages {
+            writer.write(
+                "load1",
+                "Load average over the last minute.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x.one_minute as f64)],
+            );
+            writer.write(
+                "load5",
+                "Load average over the last 5 minutes.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x.five_minutes as f64)],
+            );
+            writer.write(
+                "load15",
+                "Load average over the last 15 minutes.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x.fifteen_minutes as f64)],
+            );
+        }
+
+        if let Some(pressure) = &general.pressure {
+            let resources = [
+                ("cpu", pressure.cpu),
+                ("memory", pressure.memory),
+                ("io", pressure.io),
+            ];
+            let mut stalls = Vec::new();
+            for (resource, x) in resources.iter() {
+                stalls.push((*resource, "some", x.some));
+                if let Some(full) = x.full {
+                    stalls.push((*resource, "full", full));
+                }
+            }
+            let pressure_samples = |value: fn(&PressureValues) -> f64| {
+                stalls
+                    .iter()
+                    .map(|(resource, kind, values)| Sample {
+                        labels: vec![
+                            ("resource", resource.to_string()),
+                            ("kind", kind.to_string()),
+                        ],
+                        value: value(values),
+                    })
+                    .collect()
+            };
+            writer.write(
+                "pressure_avg10_percent",
+                "Percentage of time stalled waiting for the resource over the last 10 seconds.",
+                MetricType::Gauge,
+                pressure_samples(|x| x.avg10 as f64),
+            );
+            writer.write(
+                "pressure_avg60_percent",
+                "Percentage of time stalled waiting for the resource over the last 60 seconds.",
+                MetricType::Gauge,
+                pressure_samples(|x| x.avg60 as f64),
+            );
+            writer.write(
+                "pressure_avg300_percent",
+                "Percentage of time stalled waiting for the resource over the last 300 seconds.",
+                MetricType::Gauge,
+                pressure_samples(|x| x.avg300 as f64),
+            );
+            writer.write(
+                "pressure_stalled_seconds_total",
+                "Total time stalled waiting for the resource in seconds.",
+                MetricType::Counter,
+                pressure_samples(|x| x.total_microseconds as f64 / 1_000_000.0),
+            );
+        }
+    }
+
+    if let Some(cpu) = &stats.cpu {
+        if let Some(x) = &cpu.per_logical_cpu_load_percent {
+            writer.write(
+                "cpu_load_percent",
+                "Load percentage of each logical CPU.",
+                MetricType::Gauge,
+                x.iter()
+                    .enumerate()
+                    .map(|(i, load)| Sample {
+                        labels: vec![("cpu", i.to_string())],
+                        value: *load as f64,
+                    })
+                    .collect(),
+            );
+        }
+        if let Some(x) = cpu.aggregate_load_percent {
+            writer.write(
+                "cpu_aggregate_load_percent",
+                "Load percentage of the CPU as a whole.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x as f64)],
+            );
+        }
+        if let Some(x) = &cpu.aggregate_breakdown {
+            let modes = [
+                ("user", x.user),
+                ("nice", x.nice),
+                ("system", x.system),
+                ("interrupt", x.interrupt),
+                ("idle", x.idle),
+                ("iowait", x.iowait),
+                ("steal", x.steal),
+            ];
+            writer.write(
+                "cpu_aggregate_mode_percent",
+                "Percentage of time the CPU as a whole spent in each mode.",
+                MetricType::Gauge,
+                modes
+                    .iter()
+                    .map(|(mode, value)| Sample {
+                        labels: vec![("mode", mode.to_string())],
+                        value: *value as f64,
+                    })
+                    .collect(),
+            );
+        }
+
+        if let Some(x) = cpu.temp_celsius {
+            writer.write(
+                "cpu_temperature_celsius",
+                "Temperature of the CPU in degrees Celsius.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x as f64)],
+            );
+        }
+    }
+
+    if let Some(sensors) = &stats.sensors {
+        let sensor_labels = |chip: &str, label: &str| {
+            vec![("chip", chip.to_string()), ("sensor", label.to_string())]
+        };
+        writer.write(
+            "sensor_temperature_celsius",
+            "Temperature reported by the sensor in degrees Celsius.",
+            MetricType::Gauge,
+            sensors
+                .temperatures
+                .iter()
+                .map(|x| Sample {
+                    labels: sensor_labels(&x.chip, &x.label),
+                    value: x.temp_celsius as f64,
+                })
+                .collect(),
+        );
+        writer.write(
+            "sensor_critical_temperature_celsius",
+            "Temperature in degrees Celsius above which the sensor is considered critically hot.",
+            MetricType::Gauge,
+            sensors
+                .temperatures
+                .iter()
+                .filter_map(|x| {
+                    x.critical_celsius.map(|critical| Sample {
+                        labels: sensor_labels(&x.chip, &x.label),
+                        value: critical as f64,
+                    })
+                })
+                .collect(),
+        );
+        writer.write(
+            "fan_speed_rpm",
+            "Speed of the fan in revolutions per minute.",
+            MetricType::Gauge,
+            sensors
+                .fans
+                .iter()
+                .map(|x| Sample {
+                    labels: sensor_labels(&x.chip, &x.label),
+                    value: x.rpm as f64,
+                })
+                .collect(),
+        );
+    }
+
+    if let Some(x) = &stats.memory {
+        writer.write(
+            "memory_used_bytes",
+            "Memory used in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.used_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "memory_total_bytes",
+            "Total memory in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.total_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "memory_available_bytes",
+            "Memory available for starting new applications in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.available_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "memory_buffers_bytes",
+            "Memory used for block device buffers in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.buffers_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "memory_cached_bytes",
+            "Memory used for the page cache in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.cached_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "memory_shared_bytes",
+            "Memory used for shared memory and tmpfs in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.shared_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "swap_used_bytes",
+            "Swap space used in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.swap_used_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "swap_total_bytes",
+            "Total swap space in bytes.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled((x.swap_total_mb * BYTES_PER_MB) as f64)],
+        );
+        writer.write(
+            "huge_pages_total",
+            "Total number of huge pages.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled(x.huge_pages_total as f64)],
+        );
+        writer.write(
+            "huge_pages_free",
+            "Number of huge pages not yet allocated.",
+            MetricType::Gauge,
+            vec![Sample::unlabeled(x.huge_pages_free as f64)],
+        );
+    }
+
+    if let Some(mounts) = &stats.filesystems {
+        let mount_labels = |mount: &MountStats| {
+            vec![
+                ("mountpoint", mount.mounted_on.clone()),
+                ("device", mount.mounted_from.clone()),
+                ("fstype", mount.fs_type.clone()),
+            ]
+        };
+        writer.write(
+            "filesystem_used_bytes",
+            "Space used on the filesystem in bytes.",
+            MetricType::Gauge,
+            mounts
+                .iter()
+                .map(|mount| Sample {
+                    labels: mount_labels(mount),
+                    value: (mount.used_mb * BYTES_PER_MB) as f64,
+                })
+                .collect(),
+        );
+        writer.write(
+            "filesystem_size_bytes",
+            "Total space on the filesystem in bytes.",
+            MetricType::Gauge,
+            mounts
+                .iter()
+                .map(|mount| Sample {
+                    labels: mount_labels(mount),
+                    value: (mount.total_mb * BYTES_PER_MB) as f64,
+                })
+                .collect(),
+        );
+        writer.write(
+            "filesystem_inodes_used",
+            "Number of inodes used on the filesystem.",
+            MetricType::Gauge,
+            mounts
+                .iter()
+                .map(|mount| Sample {
+                    labels: mount_labels(mount),
+                    value: mount.inodes_used as f64,
+                })
+                .collect(),
+        );
+        writer.write(
+            "filesystem_inodes",
+            "Total number of inodes on the filesystem.",
+            MetricType::Gauge,
+            mounts
+                .iter()
+                .map(|mount| Sample {
+                    labels: mount_labels(mount),
+                    value: mount.inodes_total as f64,
+                })
+                .collect(),
+        );
+        writer.write(
+            "filesystem_inodes_free",
+            "Number of inodes available for new files on the filesystem.",
+            MetricType::Gauge,
+            mounts
+                .iter()
+                .map(|mount| Sample {
+                    labels: mount_labels(mount),
+                    value: mount.inodes_free as f64,
+                })
+                .collect(),
+        );
+    }
+
+    if let Some(disks) = &stats.disks {
+        let disk_samples = |value: fn(&DiskStats) -> f64| {
+            disks
+                .iter()
+                .map(|disk| Sample {
+                    labels: vec![("device", disk.name.clone())],
+                    value: value(disk),
+                })
+                .collect()
+        };
+        writer.write(
+            "disk_read_bytes_total",
+            "Total bytes read from the device.",
+            MetricType::Counter,
+            disk_samples(|x| x.read_bytes as f64),
+        );
+        writer.write(
+            "disk_written_bytes_total",
+            "Total bytes written to the device.",
+            MetricType::Counter,
+            disk_samples(|x| x.written_bytes as f64),
+        );
+        writer.write(
+            "disk_reads_completed_total",
+            "Total reads completed by the device.",
+            MetricType::Counter,
+            disk_samples(|x| x.reads as f64),
+        );
+        writer.write(
+            "disk_writes_completed_total",
+            "Total writes completed by the device.",
+            MetricType::Counter,
+            disk_samples(|x| x.writes as f64),
+        );
+        writer.write(
+            "disk_io_time_seconds_total",
+            "Total seconds the device spent doing I/O.",
+            MetricType::Counter,
+            disk_samples(|x| x.io_milliseconds as f64 / 1000.0),
+        );
+        writer.write(
+            "disk_io_time_weighted_seconds_total",
+            "Total seconds the device spent doing I/O, weighted by the number of requests in progress.",
+            MetricType::Counter,
+            disk_samples(|x| x.weighted_io_milliseconds as f64 / 1000.0),
+        );
+        writer.write(
+            "disk_io_in_progress",
+            "Number of I/O requests currently in progress on the device.",
+            MetricType::Gauge,
+            disk_samples(|x| x.io_in_progress as f64),
+        );
+    }
+
+    if let Some(network) = &stats.network {
+        if let Some(interfaces) = &network.interfaces {
+            let interface_samples = |value: fn(&NetworkInterfaceStats) -> u64| {
+                interfaces
+                    .iter()
+                    .map(|interface| Sample {
+                        labels: vec![("interface", interface.name.clone())],
+                        value: value(interface) as f64,
+                    })
+                    .collect()
+            };
+            writer.write(
+                "network_sent_bytes_total",
+                "Total bytes sent via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.sent_bytes),
+            );
+            writer.write(
+                "network_received_bytes_total",
+                "Total bytes received via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.received_bytes),
+            );
+            writer.write(
+                "network_sent_packets_total",
+                "Total packets sent via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.sent_packets),
+            );
+            writer.write(
+                "network_received_packets_total",
+                "Total packets received via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.received_packets),
+            );
+            writer.write(
+                "network_send_errors_total",
+                "Total errors that occurred while sending data via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.send_errors),
+            );
+            writer.write(
+                "network_receive_errors_total",
+                "Total errors that occurred while receiving data via the interface.",
+                MetricType::Counter,
+                interface_samples(|x| x.receive_errors),
+            );
+
+            let optional_interface_samples = |value: fn(&NetworkInterfaceStats) -> Option<f64>| {
+                interfaces
+                    .iter()
+                    .filter_map(|interface| {
+                        value(interface).map(|value| Sample {
+                            labels: vec![("interface", interface.name.clone())],
+                            value,
+                        })
+                    })
+                    .collect()
+            };
+            writer.write(
+                "network_interface_info",
+                "Information about the interface. The value is always 1.",
+                MetricType::Gauge,
+                interfaces
+                    .iter()
+                    .map(|interface| Sample {
+                        labels: vec![
+                            ("interface", interface.name.clone()),
+                            ("operstate", interface.operstate.clone().unwrap_or_default()),
+                            ("address", interface.mac_address.clone().unwrap_or_default()),
+                            ("duplex", interface.duplex.clone().unwrap_or_default()),
+                        ],
+                        value: 1.0,
+                    })
+                    .collect(),
+            );
+            writer.write(
+                "network_interface_up",
+                "Whether the operational state of the interface is up.",
+                MetricType::Gauge,
+                optional_interface_samples(|x| {
+                    x.operstate
+                        .as_ref()
+                        .map(|state| if state == "up" { 1.0 } else { 0.0 })
+                }),
+            );
+            writer.write(
+                "network_interface_mtu_bytes",
+                "Maximum transmission unit of the interface in bytes.",
+                MetricType::Gauge,
+                optional_interface_samples(|x| x.mtu.map(|mtu| mtu as f64)),
+            );
+            writer.write(
+                "network_interface_speed_bytes_per_second",
+                "Link speed of the interface in bytes per second.",
+                MetricType::Gauge,
+                optional_interface_samples(|x| {
+                    x.speed_mbps
+                        .map(|speed| (speed * BYTES_PER_MB) as f64 / BITS_PER_BYTE)
+                }),
+            );
+        }
+
+        if let Some(x) = &network.sockets {
+            writer.write(
+                "sockets_in_use",
+                "Number of sockets in use.",
+                MetricType::Gauge,
+                vec![
+                    Sample {
+                        labels: vec![("protocol", "tcp".to_string())],
+                        value: x.tcp_in_use as f64,
+                    },
+                    Sample {
+                        labels: vec![("protocol", "udp".to_string())],
+                        value: x.udp_in_use as f64,
+                    },
+                    Sample {
+                        labels: vec![("protocol", "tcp6".to_string())],
+                        value: x.tcp6_in_use as f64,
+                    },
+                    Sample {
+                        labels: vec![("protocol", "udp6".to_string())],
+                        value: x.udp6_in_use as f64,
+                    },
+                ],
+            );
+            writer.write(
+                "tcp_sockets_orphaned",
+                "Number of orphaned TCP sockets.",
+                MetricType::Gauge,
+                vec![Sample::unlabeled(x.tcp_orphaned as f64)],
+            );
+
+            let mut state_samples = Vec::new();
+            for (protocol, states) in [("tcp", &x.tcp_states), ("tcp6", &x.tcp6_states)].iter() {
+                if let Some(states) = states {
+                    let counts = [
+                        ("established", states.established),
+                        ("syn_sent", states.syn_sent),
+                        ("syn_recv", states.syn_recv),
+                        ("fin_wait1", states.fin_wait1),
+                        ("fin_wait2", states.fin_wait2),
+                        ("time_wait", states.time_wait),
+                        ("close", states.close),
+                        ("close_wait", states.close_wait),
+                        ("last_ack", states.last_ack),
+                        ("listen", states.listen),
+                        ("closing", states.closing),
+                    ];
+                    for (state, count) in counts.iter() {
+                        state_samples.push(Sample {
+                            labels: vec![
+                                ("protocol", protocol.to_string()),
+                                ("state", state.to_string()),
+                            ],
+                            value: *count as f64,
+                        });
+                    }
+                }
+            }
+            writer.write(
+                "tcp_connections",
+                "Number of TCP connections in each state.",
+                MetricType::Gauge,
+                state_samples,
+            );
+
+            if let Some(ports) = &x.listening_ports {
+                writer.write(
+                    "tcp_listening_port_info",
+                    "A TCP port being listened on. The value is always 1.",
+                    MetricType::Gauge,
+                    ports
+                        .iter()
+                        .map(|port| Sample {
+                            labels: vec![
+                                ("protocol", port.protocol.clone()),
+                                ("address", port.address.clone()),
+                                ("port", port.port.to_string()),
+                            ],
+                            value: 1.0,
+                        })
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Escapes a label value so it can be included in a Prometheus text format document.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,272 @@
+//! Stats for individual processes.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collectors::CollectionKind;
+use crate::stats::log;
+
+const DEFAULT_PROC_DIR: &str = "/proc";
+const PASSWD_FILE_PATH: &str = "/etc/passwd";
+
+/// The number of clock ticks per second to assume for CPU times in `/proc` if it can't be read from the system.
+const DEFAULT_CLOCK_TICKS_PER_SECOND: f32 = 100.0;
+
+/// Stats for a single process
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessStats {
+    /// The ID of the process
+    pub pid: u32,
+    /// The name of the process
+    pub name: String,
+    /// The command line the process was started with
+    pub command_line: String,
+    /// The name of the user running the process, or their ID if the name isn't known
+    pub user: String,
+    /// The state of the process (running, sleeping, etc.)
+    pub state: String,
+    /// Percentage of a single logical CPU used by the process since the previous collection. Can exceed 100 for processes using more than one logical CPU.
+    pub cpu_percent: f32,
+    /// Kilobytes of resident memory used by the process
+    pub resident_memory_kb: u64,
+    /// Number of threads in the process
+    pub threads: u64,
+}
+
+/// How to sort a list of process stats.
+#[derive(Debug, Clone, Copy)]
+pub enum ProcessSortOrder {
+    /// Highest CPU usage first.
+    Cpu,
+    /// Highest memory usage first.
+    Memory,
+    /// Most threads first.
+    Threads,
+    /// Lowest process ID first.
+    Pid,
+    /// Alphabetically by name.
+    Name,
+}
+
+impl ProcessSortOrder {
+    /// Parses a sort order from its name. Returns `None` if the name isn't recognized.
+    pub fn parse(name: &str) -> Option<ProcessSortOrder> {
+        match name {
+            "cpu" => Some(ProcessSortOrder::Cpu),
+            "memory" => Some(ProcessSortOrder::Memory),
+            "threads" => Some(ProcessSortOrder::Threads),
+            "pid" => Some(ProcessSortOrder::Pid),
+            "name" => Some(ProcessSortOrder::Name),
+            _ => None,
+        }
+    }
+
+    /// Sorts the provided list of process stats.
+    pub fn sort(self, processes: &mut [ProcessStats]) {
+        match self {
+            ProcessSortOrder::Cpu => {
+                processes.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent))
+            }
+            ProcessSortOrder::Memory => processes.sort_by_key(|x| Reverse(x.resident_memory_kb)),
+            ProcessSortOrder::Threads => processes.sort_by_key(|x| Reverse(x.threads)),
+            ProcessSortOrder::Pid => processes.sort_by_key(|x| x.pid),
+            ProcessSortOrder::Name => processes.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+    }
+}
+
+/// Collects stats for each running process. CPU usage is calculated from the CPU time each process used since the previous scheduled collection.
+pub struct ProcessCollector {
+    /// The directory to read process information from.
+    proc_dir: PathBuf,
+    /// The total CPU ticks used by each process as of the previous scheduled collection, and the time of that collection, if there's been one.
+    previous_collection: Mutex<Option<(HashMap<u32, u64>, Instant)>>,
+    /// The number of clock ticks per second used for CPU times in `/proc`.
+    clock_ticks_per_second: f32,
+}
+
+impl ProcessCollector {
+    /// Creates a `ProcessCollector` that reads from `/proc`.
+    pub fn new() -> ProcessCollector {
+        ProcessCollector::with_proc_dir(DEFAULT_PROC_DIR.into())
+    }
+
+    /// Creates a `ProcessCollector` that reads from the provided directory instead of `/proc`.
+    pub fn with_proc_dir(proc_dir: PathBuf) -> ProcessCollector {
+        ProcessCollector {
+            proc_dir,
+            previous_collection: Mutex::new(None),
+            clock_ticks_per_second: read_clock_ticks_per_second(),
+        }
+    }
+
+    /// Gets stats for each running process. Returns `None` if an error occurs. Only scheduled collections replace the CPU times later collections are compared to.
+    pub fn collect_processes(&self, kind: CollectionKind) -> Option<Vec<ProcessStats>> {
+        let entries = match read_dir(&self.proc_dir) {
+            Ok(x) => x,
+            Err(e) => {
+                log("Error getting processes: ", e);
+                return None;
+            }
+        };
+
+        let now = Instant::now();
+        let mut previous_collection = self.previous_collection.lock().unwrap();
+        let empty_cpu_ticks = HashMap::new();
+        let (previous_cpu_ticks, elapsed_seconds) = match &*previous_collection {
+            Some((cpu_ticks, time)) => (cpu_ticks, Some(now.duration_since(*time).as_secs_f32())),
+            None => (&empty_cpu_ticks, None),
+        };
+        let user_names = load_user_names();
+
+        let mut processes = Vec::new();
+        let mut cpu_ticks = HashMap::new();
+        for entry in entries.flatten() {
+            let pid = match entry.file_name().to_str().and_then(|x| x.parse().ok()) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            // processes can exit at any time, so errors reading them are expected and ignored
+            if let Ok((process, ticks)) = read_process(&entry.path(), pid, &user_names) {
+                let cpu_percent = match (previous_cpu_ticks.get(&pid), elapsed_seconds) {
+                    (Some(previous_ticks), Some(elapsed)) if elapsed > 0.0 => {
+                        (ticks.saturating_sub(*previous_ticks) as f32 / self.clock_ticks_per_second)
+                            / elapsed
+                            * 100.0
+                    }
+                    _ => 0.0,
+                };
+                cpu_ticks.insert(pid, ticks);
+                processes.push(ProcessStats {
+                    cpu_percent,
+                    ..process
+                });
+            }
+        }
+
+        if kind == CollectionKind::Scheduled {
+            *previous_collection = Some((cpu_ticks, now));
+        }
+
+        Some(processes)
+    }
+}
+
+impl Default for ProcessCollector {
+    fn default() -> Self {
+        ProcessCollector::new()
+    }
+}
+
+/// Reads the number of clock ticks per second used for CPU times in `/proc` from the system, falling back to the usual value if it can't be read.
+fn read_clock_ticks_per_second() -> f32 {
+    // safe because sysconf has no preconditions and doesn't touch any memory
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        x if x > 0 => x as f32,
+        _ => DEFAULT_CLOCK_TICKS_PER_SECOND,
+    }
+}
+
+/// Reads stats for a single process from its directory in `/proc`. Returns the stats along with the total CPU ticks the process has used.
+fn read_process(
+    dir: &Path,
+    pid: u32,
+    user_names: &HashMap<u32, String>,
+) -> io::Result<(ProcessStats, u64)> {
+    let stat = read_to_string(dir.join("stat"))?;
+    let status = read_to_string(dir.join("status"))?;
+    let command_line = read_to_string(dir.join("cmdline"))?
+        .split('\0')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    // the name in the stat file can contain spaces and parentheses, so the fields after it are found by looking after the last parenthesis
+    let fields_after_name: Vec<&str> = match stat.rfind(')') {
+        Some(i) => stat[(i + 1)..].split_whitespace().collect(),
+        None => return Err(invalid_data("missing process name in stat file")),
+    };
+    // utime and stime are the 14th and 15th fields, and the fields after the name start at the 3rd
+    let user_ticks = parse_field(fields_after_name.get(11))?;
+    let system_ticks = parse_field(fields_after_name.get(12))?;
+
+    let status_values: HashMap<&str, &str> = status
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key, value.trim()))
+        .collect();
+    let uid: Option<u32> = status_values
+        .get("Uid")
+        .and_then(|x| x.split_whitespace().next())
+        .and_then(|x| x.parse().ok());
+    let user = match uid {
+        Some(uid) => user_names
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string()),
+        None => "".to_string(),
+    };
+    let resident_memory_kb = status_values
+        .get("VmRSS")
+        .and_then(|x| x.split_whitespace().next())
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(0);
+
+    Ok((
+        ProcessStats {
+            pid,
+            name: status_values.get("Name").unwrap_or(&"").to_string(),
+            command_line,
+            user,
+            state: status_values.get("State").unwrap_or(&"").to_string(),
+            cpu_percent: 0.0,
+            resident_memory_kb,
+            threads: parse_field(status_values.get("Threads"))?,
+        },
+        user_ticks + system_ticks,
+    ))
+}
+
+/// Parses a numeric field from a `/proc` file.
+fn parse_field(field: Option<&&str>) -> io::Result<u64> {
+    match field {
+        Some(x) => x
+            .parse()
+            .map_err(|_| invalid_data(&format!("invalid numeric field: {}", x))),
+        None => Err(invalid_data("missing field")),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Loads the name of each user by their ID. Returns an empty map if an error occurs.
+fn load_user_names() -> HashMap<u32, String> {
+    match read_to_string(PASSWD_FILE_PATH) {
+        Ok(passwd) => passwd
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse().ok()?;
+                Some((uid, name.to_string()))
+            })
+            .collect(),
+        Err(e) => {
+            log("Error getting user names: ", e);
+            HashMap::new()
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Stats for hardware sensors.
+
+use std::{
+    fs::{read_dir, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::log;
+
+const HWMON_DIR: &str = "/sys/class/hwmon";
+const THERMAL_DIR: &str = "/sys/class/thermal";
+const THERMAL_ZONE_PREFIX: &str = "thermal_zone";
+const THERMAL_CHIP_NAME: &str = "thermal";
+const MILLIDEGREES_PER_DEGREE: f32 = 1000.0;
+
+/// Stats for hardware sensors
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SensorStats {
+    /// Stats for each temperature sensor
+    pub temperatures: Vec<TemperatureSensorStats>,
+    /// Stats for each fan
+    pub fans: Vec<FanStats>,
+}
+
+/// Stats for a temperature sensor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemperatureSensorStats {
+    /// The name of the chip the sensor is on
+    pub chip: String,
+    /// The label of the sensor
+    pub label: String,
+    /// The current temperature in degrees Celsius
+    pub temp_celsius: f32,
+    /// The temperature in degrees Celsius above which the sensor is considered hot
+    pub high_celsius: Option<f32>,
+    /// The temperature in degrees Celsius above which the sensor is considered critically hot
+    pub critical_celsius: Option<f32>,
+}
+
+/// Stats for a fan
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FanStats {
+    /// The name of the chip the fan is connected to
+    pub chip: String,
+    /// The label of the fan
+    pub label: String,
+    /// The current speed of the fan in revolutions per minute
+    pub rpm: u64,
+}
+
+impl TemperatureSensorStats {
+    /// Gets the name to display for this sensor.
+    pub fn display_name(&self) -> String {
+        format!("{} {}", self.chip, self.label)
+    }
+}
+
+impl FanStats {
+    /// Gets the name to display for this fan.
+    pub fn display_name(&self) -> String {
+        format!("{} {}", self.chip, self.label)
+    }
+}
+
+impl SensorStats {
+    /// Gets stats for the sensors in `/sys/class/hwmon` and `/sys/class/thermal`. Returns `None` if neither is available.
+    pub fn read() -> Option<SensorStats> {
+        SensorStats::read_from(Path::new(HWMON_DIR), Path::new(THERMAL_DIR))
+    }
+
+    /// Gets stats for the sensors in the provided directories. Returns `None` if neither is available.
+    ///
+    /// # Arguments
+    /// * `hwmon_dir` - The directory to find hwmon devices in.
+    /// * `thermal_dir` - The directory to find thermal zones in.
+    pub fn read_from(hwmon_dir: &Path, thermal_dir: &Path) -> Option<SensorStats> {
+        if !hwmon_dir.exists() && !thermal_dir.exists() {
+            return None;
+        }
+
+        let mut stats = SensorStats {
+            temperatures: Vec::new(),
+            fans: Vec::new(),
+        };
+        if hwmon_dir.exists() {
+            match sorted_entries(hwmon_dir) {
+                Ok(devices) => {
+                    for device in devices {
+                        read_hwmon_device(&device, &mut stats);
+                    }
+                }
+                Err(e) => log("Error getting hwmon devices: ", e),
+            }
+        }
+        if thermal_dir.exists() {
+            match sorted_entries(thermal_dir) {
+                Ok(zones) => stats.temperatures.extend(
+                    zones
+                        .iter()
+                        .filter(|zone| is_thermal_zone(zone))
+                        .filter_map(|zone| read_thermal_zone(zone)),
+                ),
+                Err(e) => log("Error getting thermal zones: ", e),
+            }
+        }
+
+        Some(stats)
+    }
+}
+
+/// Adds stats for the temperature sensors and fans of an hwmon device.
+fn read_hwmon_device(device_dir: &Path, stats: &mut SensorStats) {
+    let chip = match read_trimmed(&device_dir.join("name")) {
+        Some(x) => x,
+        None => file_name(device_dir),
+    };
+
+    let files = match sorted_entries(device_dir) {
+        Ok(x) => x,
+        Err(e) => {
+            log(
+                &format!("Error getting sensors for hwmon device {}: ", chip),
+                e,
+            );
+            return;
+        }
+    };
+    for file in files {
+        let file_name = file_name(&file);
+        if let Some(sensor) = sensor_prefix(&file_name, "temp") {
+            if let Some(temp) = read_millidegrees(&device_dir.join(format!("{}_input", sensor))) {
+                stats.temperatures.push(TemperatureSensorStats {
+                    chip: chip.clone(),
+                    label: read_label(device_dir, sensor),
+                    temp_celsius: temp,
+                    high_celsius: read_millidegrees(&device_dir.join(format!("{}_max", sensor))),
+                    critical_celsius: read_millidegrees(
+                        &device_dir.join(format!("{}_crit", sensor)),
+                    ),
+                });
+            }
+        } else if let Some(sensor) = sensor_prefix(&file_name, "fan") {
+            if let Some(rpm) = read_trimmed(&device_dir.join(format!("{}_input", sensor)))
+                .and_then(|x| x.parse().ok())
+            {
+                stats.fans.push(FanStats {
+                    chip: chip.clone(),
+                    label: read_label(device_dir, sensor),
+                    rpm,
+                });
+            }
+        }
+    }
+}
+
+/// Gets the stats for a thermal zone. Returns `None` if its temperature can't be read.
+fn read_thermal_zone(zone_dir: &Path) -> Option<TemperatureSensorStats> {
+    let temp_celsius = read_millidegrees(&zone_dir.join("temp"))?;
+    let label = read_trimmed(&zone_dir.join("type")).unwrap_or_else(|| file_name(zone_dir));
+
+    let mut high_celsius = None;
+    let mut critical_celsius = None;
+    for i in 0.. {
+        let trip_type = match read_trimmed(&zone_dir.join(format!("trip_point_{}_type", i))) {
+            Some(x) => x,
+            None => break,
+        };
+        let trip_temp = read_millidegrees(&zone_dir.join(format!("trip_point_{}_temp", i)));
+        match trip_type.as_str() {
+            "critical" => critical_celsius = critical_celsius.or(trip_temp),
+            "hot" | "passive" => high_celsius = high_celsius.or(trip_temp),
+            _ => (),
+        }
+    }
+
+    Some(TemperatureSensorStats {
+        chip: THERMAL_CHIP_NAME.to_string(),
+        label,
+        temp_celsius,
+        high_celsius,
+        critical_celsius,
+    })
+}
+
+/// Gets the sensor name (like `temp1`) from the name of its input file (like `temp1_input`), if the file is an input file for the provided kind of sensor.
+fn sensor_prefix<'a>(file_name: &'a str, kind: &str) -> Option<&'a str> {
+    let sensor = file_name.strip_suffix("_input")?;
+    let number = sensor.strip_prefix(kind)?;
+    if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+        Some(sensor)
+    } else {
+        None
+    }
+}
+
+/// Whether the provided directory is a thermal zone, as opposed to a cooling device.
+fn is_thermal_zone(dir: &Path) -> bool {
+    file_name(dir).starts_with(THERMAL_ZONE_PREFIX)
+}
+
+/// Reads the label for a sensor, falling back to the sensor's name if it doesn't have one.
+fn read_label(device_dir: &Path, sensor: &str) -> String {
+    read_trimmed(&device_dir.join(format!("{}_label", sensor)))
+        .unwrap_or_else(|| sensor.to_string())
+}
+
+/// Reads a temperature in millidegrees Celsius from a file and converts it to degrees Celsius.
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    read_trimmed(path)?
+        .parse::<f32>()
+        .ok()
+        .map(|x| x / MILLIDEGREES_PER_DEGREE)
+}
+
+/// Reads the trimmed contents of a file. Returns `None` if the file can't be read, since sensors often don't provide every file.
+fn read_trimmed(path: &Path) -> Option<String> {
+    read_to_string(path).ok().map(|x| x.trim().to_string())
+}
+
+/// Gets the paths of the entries in a directory, sorted so sensors are always listed in the same order.
+fn sorted_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = read_dir(dir)?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+
+    Ok(entries)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
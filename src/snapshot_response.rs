@@ -0,0 +1,42 @@
+//! Responses for stats from a single snapshot.
+
+use chrono::{DateTime, Local, SecondsFormat};
+use rocket::{
+    response::{self, Responder},
+    serde::json::Json,
+    Request, Response,
+};
+use serde::Serialize;
+
+/// The header that holds the time at which the stats in a response were collected.
+const COLLECTION_TIME_HEADER: &str = "X-Collection-Time";
+
+/// A response with stats from a single snapshot. The time the snapshot was collected is included in the `X-Collection-Time` header, so responses for different categories of stats can be matched up with each other.
+pub struct SnapshotResponse<T> {
+    /// The stats to respond with.
+    stats: T,
+    /// The time at which the snapshot the stats are from was collected.
+    collection_time: DateTime<Local>,
+}
+
+impl<T> SnapshotResponse<T> {
+    /// Creates a `SnapshotResponse` with the provided stats from the snapshot collected at `collection_time`.
+    pub fn new(stats: T, collection_time: DateTime<Local>) -> SnapshotResponse<T> {
+        SnapshotResponse {
+            stats,
+            collection_time,
+        }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for SnapshotResponse<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(Json(self.stats).respond_to(request)?)
+            .raw_header(
+                COLLECTION_TIME_HEADER,
+                self.collection_time
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, false),
+            )
+            .ok()
+    }
+}
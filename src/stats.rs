@@ -1,54 +1,145 @@
 //! A collection of system stats.
 
-use std::{io::Error, thread};
+use std::{
+    collections::BTreeMap,
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+    sync::Arc,
+};
 
 use chrono::{DateTime, Local};
 use serde::Deserialize;
 use serde::Serialize;
-use systemstat::{
-    saturating_sub_bytes, ByteSize, Duration, IpAddr, NetworkAddrs, Platform, System,
-};
+use systemstat::{saturating_sub_bytes, ByteSize, IpAddr, NetworkAddrs, Platform, System};
+
+use crate::cgroups::CgroupStats;
+use crate::consolidation::ConsolidationStrategy;
+use crate::filters::{InterfaceFilter, MountFilter};
+use crate::processes::ProcessStats;
+use crate::sensors::SensorStats;
 
-const BYTES_PER_MB: u64 = 1_000_000;
+pub const BYTES_PER_MB: u64 = 1_000_000;
+const BYTES_PER_SECTOR: u64 = 512;
+const MEMINFO_PATH: &str = "/proc/meminfo";
+const PROC_STAT_PATH: &str = "/proc/stat";
+const PRESSURE_DIR: &str = "/proc/pressure";
+const NET_CLASS_DIR: &str = "/sys/class/net";
+const PROC_NET_TCP_PATH: &str = "/proc/net/tcp";
+const PROC_NET_TCP6_PATH: &str = "/proc/net/tcp6";
+/// The code for the listening state in `/proc/net/tcp`.
+const TCP_LISTEN_STATE: u8 = 0x0A;
 
 /// All system stats
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AllStats {
     /// General system stats
-    pub general: GeneralStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub general: Option<GeneralStats>,
     /// CPU stats
-    pub cpu: CpuStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<CpuStats>,
     /// Memory stats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub memory: Option<MemoryStats>,
     /// Stats for each mounted filesystem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub filesystems: Option<Vec<MountStats>>,
+    /// I/O stats for each block device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disks: Option<Vec<DiskStats>>,
+    /// Stats for hardware sensors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensors: Option<SensorStats>,
     /// Network stats
-    pub network: NetworkStats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkStats>,
+    /// Stats for each running process. These aren't serialized, so they aren't kept in the history, since there are so many of them
+    #[serde(skip)]
+    pub processes: Option<Arc<Vec<ProcessStats>>>,
+    /// Stats for each cgroup, if cgroup v2 is in use. Like process stats, these aren't serialized
+    #[serde(skip)]
+    pub cgroups: Option<Arc<Vec<CgroupStats>>>,
     /// The time at which the stats were collected
     pub collection_time: DateTime<Local>,
+    /// How current the stats from each collector are, by collector name
+    #[serde(default)]
+    pub collectors: BTreeMap<String, CollectorStatus>,
+    /// For consolidated stats, the other ways the stats were consolidated. The rest of the stats are averages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consolidated: Option<Box<ConsolidatedStats>>,
+}
+
+/// The stats consolidated in ways other than averaging them
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidatedStats {
+    /// The lowest value of each stat
+    pub min: AllStats,
+    /// The highest value of each stat
+    pub max: AllStats,
+    /// The 95th percentile of each stat, if it's enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p95: Option<AllStats>,
+    /// The most recent value of each stat
+    pub last: AllStats,
+}
+
+/// How current the stats from a single collector are
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectorStatus {
+    /// The time at which the collector's stats were collected
+    pub collection_time: DateTime<Local>,
+    /// Number of seconds between when the collector's stats were collected and when these stats were assembled
+    pub age_seconds: f32,
+    /// Whether the collector's most recent collection took longer than its timeout, or its current one has been running longer than it
+    pub timed_out: bool,
 }
 
 impl AllStats {
-    /// Gets all stats for the provided system.
+    /// Creates stats with nothing collected yet, for collectors to fill in.
     ///
     /// # Arguments
-    /// * `sys` - The system to get stats from.
-    /// * `cpu_sample_duration` - The amount of time to take to sample CPU load. Note that this function will block the thread it's in for this duration before returning.
-    pub fn from(sys: &System, cpu_sample_duration: Duration) -> AllStats {
+    /// * `collection_time` - The time at which the stats were collected.
+    pub fn empty(collection_time: DateTime<Local>) -> AllStats {
         AllStats {
-            general: GeneralStats::from(&sys),
-            cpu: CpuStats::from(&sys, cpu_sample_duration),
-            memory: MemoryStats::from(&sys),
-            filesystems: MountStats::from(&sys),
-            network: NetworkStats::from(&sys),
-            collection_time: Local::now(),
+            general: None,
+            cpu: None,
+            memory: None,
+            filesystems: None,
+            disks: None,
+            sensors: None,
+            network: None,
+            processes: None,
+            cgroups: None,
+            collection_time,
+            collectors: BTreeMap::new(),
+            consolidated: None,
+        }
+    }
+
+    /// Gets these stats consolidated with the provided strategy. If these stats weren't consolidated that way, or weren't consolidated at all, they're returned as-is.
+    pub fn consolidated_with(&self, strategy: ConsolidationStrategy) -> &AllStats {
+        let consolidated = match &self.consolidated {
+            Some(x) => x,
+            None => return self,
+        };
+
+        match strategy {
+            ConsolidationStrategy::Average => self,
+            ConsolidationStrategy::Min => &consolidated.min,
+            ConsolidationStrategy::Max => &consolidated.max,
+            ConsolidationStrategy::P95 => consolidated.p95.as_ref().unwrap_or(self),
+            ConsolidationStrategy::Last => &consolidated.last,
         }
     }
 }
 
 /// General system stats
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct GeneralStats {
     /// Number of seconds the system has been running
@@ -57,6 +148,9 @@ pub struct GeneralStats {
     pub boot_timestamp: Option<i64>,
     /// Load average values for the system
     pub load_averages: Option<LoadAverages>,
+    /// Pressure stall information for the system
+    #[serde(default)]
+    pub pressure: Option<PressureStats>,
 }
 
 /// Load average values
@@ -102,17 +196,127 @@ impl GeneralStats {
             }
         };
 
+        let pressure = match PressureStats::read() {
+            Ok(x) => Some(x),
+            Err(e) => {
+                log("Error getting pressure stall information: ", e);
+                None
+            }
+        };
+
         GeneralStats {
             uptime_seconds,
             boot_timestamp,
             load_averages,
+            pressure,
         }
     }
 }
 
-/// CPU stats
+/// Pressure stall information, which describes how much time tasks spent waiting for each resource
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct PressureStats {
+    /// Pressure on the CPU
+    pub cpu: ResourcePressure,
+    /// Pressure on memory
+    pub memory: ResourcePressure,
+    /// Pressure on I/O
+    pub io: ResourcePressure,
+}
+
+/// Pressure on a single resource
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePressure {
+    /// Time during which at least some tasks were stalled waiting for the resource
+    pub some: PressureValues,
+    /// Time during which all non-idle tasks were stalled waiting for the resource at the same time. Not present for the CPU on kernels older than 5.13.
+    pub full: Option<PressureValues>,
+}
+
+/// Pressure values for a single kind of stall
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PressureValues {
+    /// Percentage of time stalled over the last 10 seconds
+    pub avg10: f32,
+    /// Percentage of time stalled over the last 60 seconds
+    pub avg60: f32,
+    /// Percentage of time stalled over the last 300 seconds
+    pub avg300: f32,
+    /// Total time stalled in microseconds
+    pub total_microseconds: u64,
+}
+
+impl PressureStats {
+    /// Reads pressure stall information from `/proc/pressure`. Returns a "Not supported" error if the kernel doesn't provide it.
+    pub fn read() -> Result<PressureStats, Error> {
+        if !Path::new(PRESSURE_DIR).exists() {
+            return Err(Error::other("Not supported"));
+        }
+
+        Ok(PressureStats {
+            cpu: ResourcePressure::read("cpu")?,
+            memory: ResourcePressure::read("memory")?,
+            io: ResourcePressure::read("io")?,
+        })
+    }
+}
+
+impl ResourcePressure {
+    /// Reads the pressure on the resource with the provided name from `/proc/pressure`.
+    fn read(resource: &str) -> Result<ResourcePressure, Error> {
+        let contents = read_to_string(Path::new(PRESSURE_DIR).join(resource))?;
+        let mut some = None;
+        let mut full = None;
+        for line in contents.lines() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("some") => some = Some(PressureValues::parse(parts)?),
+                Some("full") => full = Some(PressureValues::parse(parts)?),
+                _ => (),
+            }
+        }
+
+        match some {
+            Some(some) => Ok(ResourcePressure { some, full }),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("no pressure values found for {}", resource),
+            )),
+        }
+    }
+}
+
+impl PressureValues {
+    /// Parses the `key=value` pairs from a line in a `/proc/pressure` file.
+    fn parse<'a>(pairs: impl Iterator<Item = &'a str>) -> Result<PressureValues, Error> {
+        let mut values = PressureValues::default();
+        for pair in pairs {
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid pressure value: {}", pair),
+                )
+            };
+            let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "avg10" => values.avg10 = value.parse().map_err(|_| invalid())?,
+                "avg60" => values.avg60 = value.parse().map_err(|_| invalid())?,
+                "avg300" => values.avg300 = value.parse().map_err(|_| invalid())?,
+                "total" => values.total_microseconds = value.parse().map_err(|_| invalid())?,
+                _ => (),
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// CPU stats
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct CpuStats {
     /// Load percentages for each logical CPU
     pub per_logical_cpu_load_percent: Option<Vec<f32>>,
@@ -120,45 +324,70 @@ pub struct CpuStats {
     pub aggregate_load_percent: Option<f32>,
     /// Temperature of the CPU in degrees Celsius
     pub temp_celsius: Option<f32>,
+    /// Breakdown of how each logical CPU spent its time
+    #[serde(default)]
+    pub per_logical_cpu_breakdown: Option<Vec<CpuBreakdown>>,
+    /// Breakdown of how the CPU as a whole spent its time
+    #[serde(default)]
+    pub aggregate_breakdown: Option<CpuBreakdown>,
+}
+
+/// Percentages of time a CPU spent in each state
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuBreakdown {
+    /// Percentage of time spent running user processes
+    pub user: f32,
+    /// Percentage of time spent running niced user processes
+    pub nice: f32,
+    /// Percentage of time spent running the kernel
+    pub system: f32,
+    /// Percentage of time spent servicing interrupts
+    pub interrupt: f32,
+    /// Percentage of time spent idle
+    pub idle: f32,
+    /// Percentage of time spent idle while waiting for I/O to complete
+    pub iowait: f32,
+    /// Percentage of time stolen by the hypervisor to run other virtual machines
+    pub steal: f32,
 }
 
 impl CpuStats {
-    /// Gets CPU stats for the provided system.
+    /// Gets CPU stats for the provided system. CPU load is calculated from the CPU time used since the previous snapshot, so it isn't available the first time this is called.
     ///
     /// # Arguments
     /// * `sys` - The system to get stats from.
-    /// * `sample_duration` - The amount of time to take to sample CPU load. Note that this function will block the thread it's in for this duration before returning.
-    pub fn from(sys: &System, sample_duration: Duration) -> CpuStats {
-        let cpu_load = sys.cpu_load();
-        let cpu_load_aggregate = sys.cpu_load_aggregate();
-        thread::sleep(sample_duration);
-        let per_logical_cpu_load_percent = match cpu_load {
-            Ok(x) => match x.done() {
-                Ok(cpus) => Some(cpus.iter().map(|cpu| (1.0 - cpu.idle) * 100.0).collect()),
-                Err(e) => {
-                    log("Error getting per logical CPU load: ", e);
-                    None
-                }
-            },
+    /// * `previous_snapshot` - The CPU times from the previous time stats were gathered. Will be replaced with the current CPU times.
+    pub fn from(sys: &System, previous_snapshot: &mut Option<CpuSnapshot>) -> CpuStats {
+        let snapshot = match CpuSnapshot::read() {
+            Ok(x) => Some(x),
             Err(e) => {
-                log("Error getting per logical CPU load: ", e);
+                log("Error getting CPU times: ", e);
                 None
             }
         };
 
-        let aggregate_load_percent = match cpu_load_aggregate {
-            Ok(x) => match x.done() {
-                Ok(cpu) => Some((1.0 - cpu.idle) * 100.0),
-                Err(e) => {
-                    log("Error getting aggregate CPU load: ", e);
-                    None
-                }
-            },
-            Err(e) => {
-                log("Error getting aggregate CPU load: ", e);
-                None
-            }
-        };
+        let (per_logical_cpu_breakdown, aggregate_breakdown) =
+            match (&*previous_snapshot, &snapshot) {
+                (Some(previous), Some(current)) => (
+                    Some(
+                        current
+                            .per_logical_cpu
+                            .iter()
+                            .zip(&previous.per_logical_cpu)
+                            .map(|(current, previous)| current.breakdown_since(previous))
+                            .collect::<Vec<CpuBreakdown>>(),
+                    ),
+                    Some(current.aggregate.breakdown_since(&previous.aggregate)),
+                ),
+                _ => (None, None),
+            };
+        *previous_snapshot = snapshot;
+
+        let per_logical_cpu_load_percent = per_logical_cpu_breakdown
+            .as_ref()
+            .map(|x| x.iter().map(CpuBreakdown::load_percent).collect());
+        let aggregate_load_percent = aggregate_breakdown.as_ref().map(CpuBreakdown::load_percent);
 
         let temp_celsius = match sys.cpu_temp() {
             Ok(x) => Some(x),
@@ -172,6 +401,126 @@ impl CpuStats {
             per_logical_cpu_load_percent,
             aggregate_load_percent,
             temp_celsius,
+            per_logical_cpu_breakdown,
+            aggregate_breakdown,
+        }
+    }
+}
+
+impl CpuBreakdown {
+    /// Gets the percentage of time that wasn't spent idle. Time spent waiting for I/O counts as busy, the same as it always has for CPU load, and is shown separately in the breakdown.
+    pub fn load_percent(&self) -> f32 {
+        100.0 - self.idle
+    }
+}
+
+/// A snapshot of the cumulative time the CPUs have spent in each state
+#[derive(Debug, Clone)]
+pub struct CpuSnapshot {
+    /// Times for all the CPUs combined
+    aggregate: CpuTimes,
+    /// Times for each logical CPU
+    per_logical_cpu: Vec<CpuTimes>,
+}
+
+impl CpuSnapshot {
+    /// Reads the current CPU times from `/proc/stat`.
+    pub fn read() -> Result<CpuSnapshot, Error> {
+        let stat = read_to_string(PROC_STAT_PATH)?;
+        let mut aggregate = None;
+        let mut per_logical_cpu = Vec::new();
+        for line in stat.lines() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("cpu") => aggregate = Some(CpuTimes::parse(parts)?),
+                Some(x) if x.starts_with("cpu") => per_logical_cpu.push(CpuTimes::parse(parts)?),
+                _ => (),
+            }
+        }
+
+        match aggregate {
+            Some(aggregate) => Ok(CpuSnapshot {
+                aggregate,
+                per_logical_cpu,
+            }),
+            None => Err(Error::new(
+                ErrorKind::InvalidData,
+                "no aggregate CPU times found",
+            )),
+        }
+    }
+}
+
+/// The cumulative time a CPU has spent in each state, in clock ticks
+#[derive(Debug, Clone)]
+struct CpuTimes {
+    user: u64,
+    nice: u64,
+    system: u64,
+    idle: u64,
+    iowait: u64,
+    irq: u64,
+    softirq: u64,
+    steal: u64,
+}
+
+impl CpuTimes {
+    /// Parses the times from the values in a CPU line of `/proc/stat`. Times that aren't present (which happens on older kernels) are treated as 0.
+    fn parse<'a>(values: impl Iterator<Item = &'a str>) -> Result<CpuTimes, Error> {
+        let values = values
+            .map(|x| {
+                x.parse().map_err(|e| {
+                    Error::new(ErrorKind::InvalidData, format!("invalid CPU time: {}", e))
+                })
+            })
+            .collect::<Result<Vec<u64>, Error>>()?;
+        let value = |i: usize| values.get(i).copied().unwrap_or(0);
+
+        Ok(CpuTimes {
+            user: value(0),
+            nice: value(1),
+            system: value(2),
+            idle: value(3),
+            iowait: value(4),
+            irq: value(5),
+            softirq: value(6),
+            steal: value(7),
+        })
+    }
+
+    /// Gets the total time spent in all states.
+    fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Calculates the percentage of time spent in each state since the provided previous times.
+    fn breakdown_since(&self, previous: &CpuTimes) -> CpuBreakdown {
+        let total = self.total().saturating_sub(previous.total());
+        if total == 0 {
+            return CpuBreakdown {
+                idle: 100.0,
+                ..CpuBreakdown::default()
+            };
+        }
+        let percent = |current: u64, previous: u64| {
+            (current.saturating_sub(previous) as f32 / total as f32) * 100.0
+        };
+
+        CpuBreakdown {
+            user: percent(self.user, previous.user),
+            nice: percent(self.nice, previous.nice),
+            system: percent(self.system, previous.system),
+            interrupt: percent(self.irq + self.softirq, previous.irq + previous.softirq),
+            idle: percent(self.idle, previous.idle),
+            iowait: percent(self.iowait, previous.iowait),
+            steal: percent(self.steal, previous.steal),
         }
     }
 }
@@ -180,10 +529,37 @@ impl CpuStats {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MemoryStats {
-    /// Megabytes of memory used
+    /// Megabytes of memory used, which is the total minus the available
     pub used_mb: u64,
     /// Megabytes of memory total
     pub total_mb: u64,
+    /// Megabytes of memory available for starting new applications without swapping
+    #[serde(default)]
+    pub available_mb: u64,
+    /// Megabytes of memory used for block device buffers
+    #[serde(default)]
+    pub buffers_mb: u64,
+    /// Megabytes of memory used for the page cache
+    #[serde(default)]
+    pub cached_mb: u64,
+    /// Megabytes of memory used for shared memory and tmpfs
+    #[serde(default)]
+    pub shared_mb: u64,
+    /// Megabytes of swap space total
+    #[serde(default)]
+    pub swap_total_mb: u64,
+    /// Megabytes of swap space used
+    #[serde(default)]
+    pub swap_used_mb: u64,
+    /// Number of huge pages total
+    #[serde(default)]
+    pub huge_pages_total: u64,
+    /// Number of huge pages not yet allocated
+    #[serde(default)]
+    pub huge_pages_free: u64,
+    /// Size of each huge page in kilobytes
+    #[serde(default)]
+    pub huge_page_size_kb: u64,
 }
 
 impl MemoryStats {
@@ -191,10 +567,38 @@ impl MemoryStats {
     pub fn from(sys: &System) -> Option<MemoryStats> {
         match sys.memory() {
             Ok(mem) => {
-                let used_mem = saturating_sub_bytes(mem.total, mem.free);
+                let meminfo = &mem.platform_memory.meminfo;
+                let get = |key: &str| meminfo.get(key).copied().unwrap_or(ByteSize::b(0));
+                let buffers = get("Buffers");
+                let cached = get("Cached");
+                // MemAvailable isn't present in kernels older than 3.14, so fall back to systemstat's free memory, which already includes buffers and cache
+                let available = match meminfo.get("MemAvailable") {
+                    Some(x) => *x,
+                    None => mem.free,
+                };
+                let used_mem = saturating_sub_bytes(mem.total, available);
+                let swap_total = get("SwapTotal");
+                let swap_used = saturating_sub_bytes(swap_total, get("SwapFree"));
+                let (huge_pages_total, huge_pages_free) = match read_huge_page_counts() {
+                    Ok(x) => x,
+                    Err(e) => {
+                        log("Error getting huge page counts: ", e);
+                        (0, 0)
+                    }
+                };
+
                 Some(MemoryStats {
                     used_mb: bytes_to_mb(used_mem),
                     total_mb: bytes_to_mb(mem.total),
+                    available_mb: bytes_to_mb(available),
+                    buffers_mb: bytes_to_mb(buffers),
+                    cached_mb: bytes_to_mb(cached),
+                    shared_mb: bytes_to_mb(get("Shmem")),
+                    swap_total_mb: bytes_to_mb(swap_total),
+                    swap_used_mb: bytes_to_mb(swap_used),
+                    huge_pages_total,
+                    huge_pages_free,
+                    huge_page_size_kb: get("Hugepagesize").as_u64() / 1024,
                 })
             }
             Err(e) => {
@@ -205,6 +609,28 @@ impl MemoryStats {
     }
 }
 
+/// Reads the total and free huge page counts from `/proc/meminfo`. These aren't sizes, so systemstat doesn't include them in its memory stats.
+fn read_huge_page_counts() -> Result<(u64, u64), Error> {
+    let meminfo = read_to_string(MEMINFO_PATH)?;
+    let mut total = 0;
+    let mut free = 0;
+    for line in meminfo.lines() {
+        let mut parts = line.split_whitespace();
+        let count = |x: Option<&str>| {
+            x.and_then(|x| x.parse().ok()).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, format!("invalid line: {}", line))
+            })
+        };
+        match parts.next() {
+            Some("HugePages_Total:") => total = count(parts.next())?,
+            Some("HugePages_Free:") => free = count(parts.next())?,
+            _ => (),
+        }
+    }
+
+    Ok((total, free))
+}
+
 /// Stats for a mounted filesystem
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -219,14 +645,23 @@ pub struct MountStats {
     pub used_mb: u64,
     /// Total space for this mount in megabytes
     pub total_mb: u64,
+    /// Number of inodes used on this mount
+    #[serde(default)]
+    pub inodes_used: u64,
+    /// Total number of inodes on this mount. Some filesystems don't have a fixed number of inodes, in which case this is 0.
+    #[serde(default)]
+    pub inodes_total: u64,
+    /// Number of inodes available for new files on this mount
+    #[serde(default)]
+    pub inodes_free: u64,
 }
 
 impl MountStats {
-    /// Gets a list of mount stats for the provided system. Only mounts with more than 0 bytes of total space are included. Returns `None` if an error occurs.
-    pub fn from(sys: &System) -> Option<Vec<MountStats>> {
+    /// Gets a list of mount stats for the provided system. Only mounts with more than 0 bytes of total space that match the provided filter are included. Returns `None` if an error occurs.
+    pub fn from(sys: &System, filter: &MountFilter) -> Option<Vec<MountStats>> {
         match sys.mounts() {
-            Ok(mounts) => Some(
-                mounts
+            Ok(mounts) => {
+                let mut mount_stats = mounts
                     .into_iter()
                     .filter_map(|mount| {
                         if mount.total.as_u64() == 0 {
@@ -239,21 +674,139 @@ impl MountStats {
                                 mounted_on: mount.fs_mounted_on,
                                 used_mb: bytes_to_mb(used),
                                 total_mb: bytes_to_mb(mount.total),
+                                inodes_used: mount.files as u64,
+                                inodes_total: mount.files_total as u64,
+                                inodes_free: mount.files_avail as u64,
                             })
                         }
                     })
+                    .collect();
+                filter.apply(&mut mount_stats);
+                Some(mount_stats)
+            }
+            Err(e) => {
+                log("Error getting mounts: ", e);
+                None
+            }
+        }
+    }
+
+    /// Gets the percentage of inodes used on this mount. Returns `None` if the filesystem doesn't report a number of inodes.
+    pub fn inodes_used_percent(&self) -> Option<f64> {
+        if self.inodes_total == 0 {
+            None
+        } else {
+            Some(((self.inodes_used as f64) / (self.inodes_total as f64)) * 100.0)
+        }
+    }
+
+    /// Whether no more files can be created on this mount because all of its inodes are used.
+    pub fn inodes_exhausted(&self) -> bool {
+        self.inodes_total > 0 && self.inodes_free == 0
+    }
+}
+
+/// I/O stats for a block device
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskStats {
+    /// The name of the device
+    pub name: String,
+    /// Total bytes read from this device
+    pub read_bytes: u64,
+    /// Total bytes written to this device
+    pub written_bytes: u64,
+    /// Total number of reads completed
+    pub reads: u64,
+    /// Total number of writes completed
+    pub writes: u64,
+    /// Number of I/O requests currently in progress
+    pub io_in_progress: u64,
+    /// Total milliseconds spent doing I/O
+    pub io_milliseconds: u64,
+    /// Total milliseconds spent doing I/O, weighted by the number of requests in progress
+    pub weighted_io_milliseconds: u64,
+    /// Bytes read per second since the previous collection
+    pub read_bytes_per_second: Option<f32>,
+    /// Bytes written per second since the previous collection
+    pub written_bytes_per_second: Option<f32>,
+    /// Reads completed per second since the previous collection
+    pub reads_per_second: Option<f32>,
+    /// Writes completed per second since the previous collection
+    pub writes_per_second: Option<f32>,
+    /// Percentage of the time since the previous collection that the device was busy doing I/O
+    pub busy_percent: Option<f32>,
+    /// Average number of I/O requests in progress since the previous collection
+    pub average_queue_depth: Option<f32>,
+}
+
+impl DiskStats {
+    /// Gets I/O stats for each block device that has done any I/O on the provided system. Returns `None` if an error occurs.
+    pub fn from(sys: &System) -> Option<Vec<DiskStats>> {
+        match sys.block_device_statistics() {
+            Ok(devices) => Some(
+                devices
+                    .into_values()
+                    .filter(|device| device.read_ios > 0 || device.write_ios > 0)
+                    .map(|device| DiskStats {
+                        name: device.name,
+                        read_bytes: (device.read_sectors as u64) * BYTES_PER_SECTOR,
+                        written_bytes: (device.write_sectors as u64) * BYTES_PER_SECTOR,
+                        reads: device.read_ios as u64,
+                        writes: device.write_ios as u64,
+                        io_in_progress: device.in_flight as u64,
+                        io_milliseconds: device.io_ticks as u64,
+                        weighted_io_milliseconds: device.time_in_queue as u64,
+                        read_bytes_per_second: None,
+                        written_bytes_per_second: None,
+                        reads_per_second: None,
+                        writes_per_second: None,
+                        busy_percent: None,
+                        average_queue_depth: None,
+                    })
                     .collect(),
             ),
             Err(e) => {
-                log("Error getting mounts: ", e);
+                log("Error getting disk stats: ", e);
                 None
             }
         }
     }
+
+    /// Calculates the rates for this device since the provided previously collected stats for it.
+    ///
+    /// # Arguments
+    /// * `previous` - The stats for this device collected before these ones.
+    /// * `elapsed_seconds` - The number of seconds between the collection of `previous` and these stats.
+    pub fn calculate_rates(&mut self, previous: &DiskStats, elapsed_seconds: f32) {
+        self.read_bytes_per_second =
+            Some(rate(self.read_bytes, previous.read_bytes, elapsed_seconds));
+        self.written_bytes_per_second = Some(rate(
+            self.written_bytes,
+            previous.written_bytes,
+            elapsed_seconds,
+        ));
+        self.reads_per_second = Some(rate(self.reads, previous.reads, elapsed_seconds));
+        self.writes_per_second = Some(rate(self.writes, previous.writes, elapsed_seconds));
+        // the I/O time counters are in milliseconds, so their rates are in milliseconds per second
+        let busy_milliseconds_per_second = rate(
+            self.io_milliseconds,
+            previous.io_milliseconds,
+            elapsed_seconds,
+        );
+        self.busy_percent = Some((busy_milliseconds_per_second / 10.0).min(100.0));
+        self.average_queue_depth = Some(
+            rate(
+                self.weighted_io_milliseconds,
+                previous.weighted_io_milliseconds,
+                elapsed_seconds,
+            ) / 1000.0,
+        );
+    }
 }
 
 /// Network stats
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct NetworkStats {
     /// Stats for network interfaces
@@ -263,13 +816,53 @@ pub struct NetworkStats {
 }
 
 impl NetworkStats {
-    /// Gets network stats for the provided system.
-    pub fn from(sys: &System) -> NetworkStats {
+    /// Gets network stats for the provided system, including only the interfaces that match the provided filter.
+    pub fn from(sys: &System, filter: &InterfaceFilter) -> NetworkStats {
         NetworkStats {
-            interfaces: NetworkInterfaceStats::from(sys),
+            interfaces: NetworkInterfaceStats::from(sys, filter),
             sockets: SocketStats::from(sys),
         }
     }
+
+    /// Calculates the throughput of each interface since the provided previously collected network stats.
+    ///
+    /// # Arguments
+    /// * `previous` - The network stats collected before these ones.
+    /// * `elapsed_seconds` - The number of seconds between the collection of `previous` and these stats.
+    pub fn calculate_rates(&mut self, previous: &NetworkStats, elapsed_seconds: f32) {
+        let (interfaces, previous_interfaces) = match (&mut self.interfaces, &previous.interfaces) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return,
+        };
+
+        for interface in interfaces {
+            if let Some(previous_interface) = previous_interfaces
+                .iter()
+                .find(|x| x.name == interface.name)
+            {
+                interface.sent_bytes_per_second = Some(rate(
+                    interface.sent_bytes,
+                    previous_interface.sent_bytes,
+                    elapsed_seconds,
+                ));
+                interface.received_bytes_per_second = Some(rate(
+                    interface.received_bytes,
+                    previous_interface.received_bytes,
+                    elapsed_seconds,
+                ));
+                interface.sent_packets_per_second = Some(rate(
+                    interface.sent_packets,
+                    previous_interface.sent_packets,
+                    elapsed_seconds,
+                ));
+                interface.received_packets_per_second = Some(rate(
+                    interface.received_packets,
+                    previous_interface.received_packets,
+                    elapsed_seconds,
+                ));
+            }
+        }
+    }
 }
 
 /// Stats for a network interface
@@ -284,6 +877,12 @@ pub struct NetworkInterfaceStats {
     pub sent_mb: u64,
     /// Total megabytes received via this interface
     pub received_mb: u64,
+    /// Total bytes sent via this interface
+    #[serde(default)]
+    pub sent_bytes: u64,
+    /// Total bytes received via this interface
+    #[serde(default)]
+    pub received_bytes: u64,
     /// Total packets sent via this interface
     pub sent_packets: u64,
     /// Total packets received via this interface
@@ -292,31 +891,75 @@ pub struct NetworkInterfaceStats {
     pub send_errors: u64,
     /// Total number of errors that occured while receiving data via this interface
     pub receive_errors: u64,
+    /// Bytes sent per second via this interface since the previous collection
+    #[serde(default)]
+    pub sent_bytes_per_second: Option<f32>,
+    /// Bytes received per second via this interface since the previous collection
+    #[serde(default)]
+    pub received_bytes_per_second: Option<f32>,
+    /// Packets sent per second via this interface since the previous collection
+    #[serde(default)]
+    pub sent_packets_per_second: Option<f32>,
+    /// Packets received per second via this interface since the previous collection
+    #[serde(default)]
+    pub received_packets_per_second: Option<f32>,
+    /// The operational state of this interface (up, down, etc.)
+    #[serde(default)]
+    pub operstate: Option<String>,
+    /// The maximum transmission unit of this interface in bytes
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// The link speed of this interface in megabits per second. Not available for virtual interfaces or interfaces that are down.
+    #[serde(default)]
+    pub speed_mbps: Option<u64>,
+    /// The MAC address of this interface
+    #[serde(default)]
+    pub mac_address: Option<String>,
+    /// The duplex mode of this interface (full or half)
+    #[serde(default)]
+    pub duplex: Option<String>,
 }
 
 impl NetworkInterfaceStats {
-    /// Gets a list of network interface stats for the provided system. Returns `None` if an error occurs.
-    pub fn from(sys: &System) -> Option<Vec<NetworkInterfaceStats>> {
+    /// Gets a list of network interface stats for the provided system. Only interfaces that match the provided filter are included. Returns `None` if an error occurs.
+    pub fn from(sys: &System, filter: &InterfaceFilter) -> Option<Vec<NetworkInterfaceStats>> {
         match sys.networks() {
             Ok(interfaces) => Some(
                 interfaces
-                    .into_iter()
-                    .filter_map(|(_, interface)| match sys.network_stats(&interface.name) {
+                    .into_values()
+                    .filter(|interface| filter.includes(&interface.name))
+                    .filter_map(|interface| match sys.network_stats(&interface.name) {
                         Ok(stats) => {
                             let addresses = interface
                                 .addrs
                                 .into_iter()
                                 .filter_map(address_to_string)
                                 .collect();
+                            let sys_dir = Path::new(NET_CLASS_DIR).join(&interface.name);
                             Some(NetworkInterfaceStats {
+                                operstate: read_sys_value(&sys_dir.join("operstate")),
+                                mtu: read_sys_value(&sys_dir.join("mtu"))
+                                    .and_then(|x| x.parse().ok()),
+                                // the speed is -1 when it isn't known
+                                speed_mbps: read_sys_value(&sys_dir.join("speed"))
+                                    .and_then(|x| x.parse().ok()),
+                                mac_address: read_sys_value(&sys_dir.join("address")),
+                                duplex: read_sys_value(&sys_dir.join("duplex"))
+                                    .filter(|x| x != "unknown"),
                                 name: interface.name,
                                 addresses,
                                 sent_mb: bytes_to_mb(stats.tx_bytes),
                                 received_mb: bytes_to_mb(stats.rx_bytes),
+                                sent_bytes: stats.tx_bytes.as_u64(),
+                                received_bytes: stats.rx_bytes.as_u64(),
                                 sent_packets: stats.tx_packets,
                                 received_packets: stats.rx_packets,
                                 send_errors: stats.tx_errors,
                                 receive_errors: stats.rx_errors,
+                                sent_bytes_per_second: None,
+                                received_bytes_per_second: None,
+                                sent_packets_per_second: None,
+                                received_packets_per_second: None,
                             })
                         }
                         Err(e) => {
@@ -351,11 +994,167 @@ pub struct SocketStats {
     pub tcp6_in_use: usize,
     /// Number of IPv6 UDP sockets in use
     pub udp6_in_use: usize,
+    /// Number of IPv4 TCP connections in each state
+    #[serde(default)]
+    pub tcp_states: Option<TcpStateCounts>,
+    /// Number of IPv6 TCP connections in each state
+    #[serde(default)]
+    pub tcp6_states: Option<TcpStateCounts>,
+    /// The TCP ports being listened on
+    #[serde(default)]
+    pub listening_ports: Option<Vec<ListeningPort>>,
+}
+
+/// Number of TCP connections in each state
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpStateCounts {
+    /// Connections that are open and can transfer data
+    pub established: usize,
+    /// Connections that sent a connection request and are waiting for a response
+    pub syn_sent: usize,
+    /// Connections that received a connection request and are waiting for it to be acknowledged
+    pub syn_recv: usize,
+    /// Connections closed locally that are waiting for the close to be acknowledged
+    pub fin_wait1: usize,
+    /// Connections closed locally that are waiting for the remote end to close
+    pub fin_wait2: usize,
+    /// Closed connections that are waiting for any remaining packets to expire
+    pub time_wait: usize,
+    /// Connections that are closed
+    pub close: usize,
+    /// Connections closed by the remote end that are waiting for the local application to close them
+    pub close_wait: usize,
+    /// Connections closed by both ends that are waiting for the final acknowledgement
+    pub last_ack: usize,
+    /// Sockets listening for connections
+    pub listen: usize,
+    /// Connections closed by both ends at the same time that are waiting for acknowledgement
+    pub closing: usize,
+}
+
+impl TcpStateCounts {
+    /// Adds the counts from another set of counts to these ones.
+    pub fn add(&mut self, other: &TcpStateCounts) {
+        self.established += other.established;
+        self.syn_sent += other.syn_sent;
+        self.syn_recv += other.syn_recv;
+        self.fin_wait1 += other.fin_wait1;
+        self.fin_wait2 += other.fin_wait2;
+        self.time_wait += other.time_wait;
+        self.close += other.close;
+        self.close_wait += other.close_wait;
+        self.last_ack += other.last_ack;
+        self.listen += other.listen;
+        self.closing += other.closing;
+    }
+
+    /// Counts a connection in the state with the provided code from `/proc/net/tcp`. Unknown states are ignored.
+    fn count(&mut self, state_code: u8) {
+        match state_code {
+            0x01 => self.established += 1,
+            0x02 => self.syn_sent += 1,
+            0x03 => self.syn_recv += 1,
+            0x04 => self.fin_wait1 += 1,
+            0x05 => self.fin_wait2 += 1,
+            0x06 => self.time_wait += 1,
+            0x07 => self.close += 1,
+            0x08 => self.close_wait += 1,
+            0x09 => self.last_ack += 1,
+            0x0A => self.listen += 1,
+            0x0B => self.closing += 1,
+            _ => (),
+        }
+    }
+}
+
+/// A TCP port being listened on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct ListeningPort {
+    /// The port number
+    pub port: u16,
+    /// The protocol the port is being listened on with (tcp or tcp6)
+    pub protocol: String,
+    /// The local address being listened on
+    pub address: String,
+}
+
+/// TCP connections read from one of the `/proc/net/tcp` files.
+struct TcpConnections {
+    /// Number of connections in each state.
+    states: TcpStateCounts,
+    /// The ports being listened on.
+    listening_ports: Vec<ListeningPort>,
+}
+
+impl TcpConnections {
+    /// Reads the TCP connections from the provided `/proc/net/tcp` file.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the file.
+    /// * `protocol` - The name of the protocol the file has connections for.
+    fn read(path: &str, protocol: &str) -> Result<TcpConnections, Error> {
+        let contents = read_to_string(path)?;
+        let mut states = TcpStateCounts::default();
+        let mut listening_ports = Vec::new();
+        // the first line is a header
+        for line in contents.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (local_address, state) = match (fields.get(1), fields.get(3)) {
+                (Some(x), Some(y)) => (*x, *y),
+                _ => continue,
+            };
+            let state = match u8::from_str_radix(state, 16) {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            states.count(state);
+            if state == TCP_LISTEN_STATE {
+                if let Some(port) = parse_tcp_address(local_address, protocol) {
+                    listening_ports.push(port);
+                }
+            }
+        }
+
+        Ok(TcpConnections {
+            states,
+            listening_ports,
+        })
+    }
+}
+
+/// Parses an address from `/proc/net/tcp` (like `0100007F:0277`) into the port being listened on. The address is in hex, with each 32-bit group of the IP address in host byte order.
+fn parse_tcp_address(address: &str, protocol: &str) -> Option<ListeningPort> {
+    let (ip, port) = address.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::new();
+    for i in (0..ip.len()).step_by(8) {
+        let group = u32::from_str_radix(ip.get(i..(i + 8))?, 16).ok()?;
+        bytes.extend_from_slice(&group.to_ne_bytes());
+    }
+    let address = match bytes.len() {
+        4 => Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string(),
+        16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes);
+            Ipv6Addr::from(octets).to_string()
+        }
+        _ => return None,
+    };
+
+    Some(ListeningPort {
+        port,
+        protocol: protocol.to_string(),
+        address,
+    })
 }
 
 impl SocketStats {
     /// Gets socket stats for the provided system. Returns `None` if an error occurs.
     pub fn from(sys: &System) -> Option<SocketStats> {
+        let tcp = read_tcp_connections(PROC_NET_TCP_PATH, "tcp");
+        let tcp6 = read_tcp_connections(PROC_NET_TCP6_PATH, "tcp6");
         match sys.socket_stats() {
             Ok(stats) => Some(SocketStats {
                 tcp_in_use: stats.tcp_sockets_in_use,
@@ -363,6 +1162,9 @@ impl SocketStats {
                 udp_in_use: stats.udp_sockets_in_use,
                 tcp6_in_use: stats.tcp6_sockets_in_use,
                 udp6_in_use: stats.udp6_sockets_in_use,
+                tcp_states: tcp.as_ref().map(|x| x.states),
+                tcp6_states: tcp6.as_ref().map(|x| x.states),
+                listening_ports: listening_ports(&tcp, &tcp6),
             }),
             Err(e) => {
                 log("Error getting socket stats: ", e);
@@ -372,8 +1174,39 @@ impl SocketStats {
     }
 }
 
+/// Reads the TCP connections from the provided `/proc/net/tcp` file. Returns `None` if an error occurs.
+fn read_tcp_connections(path: &str, protocol: &str) -> Option<TcpConnections> {
+    match TcpConnections::read(path, protocol) {
+        Ok(x) => Some(x),
+        Err(e) => {
+            log(&format!("Error getting {} connections: ", protocol), e);
+            None
+        }
+    }
+}
+
+/// Combines the listening ports from IPv4 and IPv6 TCP connections, sorted by port. Returns `None` if neither are available.
+fn listening_ports(
+    tcp: &Option<TcpConnections>,
+    tcp6: &Option<TcpConnections>,
+) -> Option<Vec<ListeningPort>> {
+    if tcp.is_none() && tcp6.is_none() {
+        return None;
+    }
+
+    let mut ports: Vec<ListeningPort> = tcp
+        .iter()
+        .chain(tcp6.iter())
+        .flat_map(|x| x.listening_ports.iter().cloned())
+        .collect();
+    ports.sort();
+    ports.dedup();
+
+    Some(ports)
+}
+
 /// Logs an error message. If the error is for a stat that isn't supported, logs at debug level. Otherwise logs at error level.
-fn log(message: &str, e: Error) {
+pub fn log(message: &str, e: Error) {
     if e.to_string() == "Not supported" {
         debug!("{}{}", message, e);
     } else {
@@ -381,6 +1214,26 @@ fn log(message: &str, e: Error) {
     }
 }
 
+/// Reads the trimmed contents of a file in `/sys`. Returns `None` if the file can't be read or is empty, since many of them aren't available for every device.
+fn read_sys_value(path: &Path) -> Option<String> {
+    read_to_string(path)
+        .ok()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+}
+
+/// Calculates the per-second rate of change of a cumulative counter.
+fn rate(current: u64, previous: u64, elapsed_seconds: f32) -> f32 {
+    // counters start over from zero when they're reset, like after a reboot
+    let change = if current >= previous {
+        current - previous
+    } else {
+        current
+    };
+
+    change as f32 / elapsed_seconds
+}
+
 /// Gets the number of megabytes represented by the provided `ByteSize`.
 fn bytes_to_mb(byte_size: ByteSize) -> u64 {
     byte_size.as_u64() / BYTES_PER_MB
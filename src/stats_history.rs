@@ -1,12 +1,18 @@
 //! A history of stats.
 
-use systemstat::System;
+use chrono::{DateTime, Local};
+use rocket::tokio::sync::broadcast;
+use serde::Deserialize;
 use thread::JoinHandle;
 
+use crate::alerts::Alerts;
+use crate::collectors::CollectorRegistry;
+use crate::filters::StatsFilters;
+use crate::history_tiers::{HistoryTierConfig, HistoryTiers};
 use crate::stats::*;
 use std::{
     fs::{create_dir_all, File},
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
 };
 use std::{
     fs::{rename, OpenOptions},
@@ -20,6 +26,10 @@ use std::{
 
 const CURRENT_HISTORY_FILE_NAME: &str = "current_stats.txt";
 const OLD_HISTORY_FILE_NAME: &str = "old_stats.txt";
+/// The number of bytes to read at a time when reading a history file backwards.
+const LAST_LINE_CHUNK_SIZE: u64 = 8192;
+
+const UPDATE_CHANNEL_CAPACITY: usize = 16;
 
 /// Stats history that updates itself periodically.
 pub struct UpdatingStatsHistory {
@@ -27,6 +37,31 @@ pub struct UpdatingStatsHistory {
     _update_thread: JoinHandle<()>,
     /// The stats history.
     pub stats_history: Arc<Mutex<StatsHistory>>,
+    /// Sends updates made to the stats history.
+    update_sender: broadcast::Sender<StatsHistoryUpdate>,
+    /// The alerts evaluated against newly collected stats.
+    pub alerts: Arc<Mutex<Alerts>>,
+    /// The collectors used to gather stats.
+    pub collectors: Arc<CollectorRegistry>,
+}
+
+/// An update made to a stats history.
+#[derive(Clone)]
+pub enum StatsHistoryUpdate {
+    /// New stats were collected.
+    Collected {
+        /// The collected stats.
+        stats: AllStats,
+        /// Whether the stats were added as a new entry, rather than replacing the most recent entry.
+        new_entry: bool,
+    },
+    /// The stats collected since the last consolidation were consolidated.
+    Consolidated {
+        /// The consolidated stats.
+        stats: AllStats,
+        /// Whether the stats were added as a new entry, rather than replacing the most recent entry.
+        new_entry: bool,
+    },
 }
 
 /// Configuration for stats history persistence.
@@ -38,236 +73,303 @@ pub enum HistoryPersistenceConfig {
     Enabled {
         /// The base directory to save the stats history to.
         dir: PathBuf,
-        /// The maximum size to allow the saved stats history directory to grow to, in bytes.
+        /// The maximum size to allow the saved stats history directory to grow to, in bytes. Doesn't include the history tiers.
         size_limit: u64,
+        /// The lower resolution tiers to also save the stats history to, from highest to lowest resolution.
+        tiers: Vec<HistoryTierConfig>,
     },
 }
 
+/// A query for the stats in a history.
+pub struct HistoryQuery {
+    /// If present, only stats collected at or after this time are included.
+    pub from: Option<DateTime<Local>>,
+    /// If present, only stats collected at or before this time are included.
+    pub to: Option<DateTime<Local>>,
+    /// If present, the maximum number of entries to include. If more entries match, only the most recent ones are included.
+    pub limit: Option<usize>,
+}
+
 impl UpdatingStatsHistory {
     /// Creates an `UpdatingStatsHistory`.
     ///
     /// # Arguments
-    /// * `system` - The system to gather stats from.
-    /// * `cpu_sample_duration` - The amount of time to take to sample CPU load. Must be less than `update_frequency`.
-    /// * `update_frequency` - How often new stats should be gathered. Must be greater than `cpu_sample_duration`.
+    /// * `update_frequency` - How often the latest stats from each collector should be added to the history.
     /// * `history_size` - The maximum number of entries to keep in the history.
     /// * `consolidation_limit` - The number of times to gather stats before consolidating them and adding them to the history.
     /// * `persistence_config` - Configuration for persisting history to disk.
+    /// * `alerts` - Alerts to evaluate against newly collected stats.
+    /// * `collectors` - The collectors gathering stats.
+    /// * `filters` - The filters to apply to persisted stats loaded into the history.
     pub fn new(
-        system: System,
-        cpu_sample_duration: Duration,
         update_frequency: Duration,
         history_size: NonZeroUsize,
         consolidation_limit: NonZeroUsize,
         persistence_config: HistoryPersistenceConfig,
+        alerts: Alerts,
+        collectors: CollectorRegistry,
+        filters: &StatsFilters,
     ) -> UpdatingStatsHistory {
         //TODO instead of maintaining this list, keep a single moving average?
         let mut recent_stats = Vec::with_capacity(consolidation_limit.get());
-        let shared_stats_history = Arc::new(Mutex::new(StatsHistory::new(history_size)));
+        let initial_history = load_initial_history(history_size, &persistence_config, filters);
+        // the most recent entry in a loaded history is persisted stats, so it shouldn't be overwritten by new stats
+        let mut start_new_entry = initial_history.get_most_recent_stats().is_some();
+        let shared_stats_history = Arc::new(Mutex::new(initial_history));
         let update_thread_stats_history = Arc::clone(&shared_stats_history);
-        let update_thread = thread::spawn(move || loop {
-            let new_stats = AllStats::from(&system, cpu_sample_duration);
-            recent_stats.push(new_stats.clone());
-
-            if recent_stats.len() >= consolidation_limit.get() {
-                let consolidated_stats = consolidate_all_stats(recent_stats);
-                if let HistoryPersistenceConfig::Enabled { dir, size_limit } = &persistence_config {
-                    if let Err(e) = persist_stats(&consolidated_stats, dir, *size_limit) {
-                        //TODO use actual logging once https://github.com/SergioBenitez/Rocket/issues/21 is done
-                        println!("Error persisting stats to {:?}: {}", dir, e);
-                    }
+        let (update_sender, _) = broadcast::channel(UPDATE_CHANNEL_CAPACITY);
+        let update_thread_sender = update_sender.clone();
+        let shared_alerts = Arc::new(Mutex::new(alerts));
+        let update_thread_alerts = Arc::clone(&shared_alerts);
+        let shared_collectors = Arc::new(collectors);
+        let update_thread_collectors = Arc::clone(&shared_collectors);
+        let update_thread = thread::spawn(move || {
+            let mut history_tiers = match &persistence_config {
+                HistoryPersistenceConfig::Enabled { dir, tiers, .. } => {
+                    Some(HistoryTiers::load(dir, tiers, &update_thread_collectors))
                 }
+                HistoryPersistenceConfig::Disabled => None,
+            };
+            loop {
+                // collectors start collecting when they're created, so give them a chance to finish their first collections
+                thread::sleep(update_frequency);
 
-                {
-                    let mut history = update_thread_stats_history.lock().unwrap();
-                    history.update_most_recent_stats(consolidated_stats);
-                    history.push(new_stats);
+                let new_stats = update_thread_collectors.latest();
+                recent_stats.push(new_stats.clone());
+
+                for status in update_thread_alerts.lock().unwrap().evaluate(&new_stats) {
+                    println!("Alert is now {:?}: {}", status.state, status.message);
                 }
-                recent_stats = Vec::with_capacity(consolidation_limit.get());
-            } else {
-                let mut history = update_thread_stats_history.lock().unwrap();
-                history.update_most_recent_stats(new_stats);
-            }
 
-            thread::sleep(update_frequency - cpu_sample_duration);
+                if recent_stats.len() >= consolidation_limit.get() {
+                    let consolidated_stats = update_thread_collectors.consolidate(&recent_stats);
+                    if let HistoryPersistenceConfig::Enabled {
+                        dir, size_limit, ..
+                    } = &persistence_config
+                    {
+                        if let Err(e) = persist_stats(&consolidated_stats, dir, *size_limit) {
+                            //TODO use actual logging once https://github.com/SergioBenitez/Rocket/issues/21 is done
+                            println!("Error persisting stats to {:?}: {}", dir, e);
+                        }
+                    }
+                    if let Some(history_tiers) = &mut history_tiers {
+                        history_tiers.add(consolidated_stats.clone(), &update_thread_collectors);
+                    }
+
+                    let consolidated_new_entry = start_new_entry;
+                    {
+                        let mut history = update_thread_stats_history.lock().unwrap();
+                        if start_new_entry {
+                            history.push(consolidated_stats.clone());
+                            start_new_entry = false;
+                        } else {
+                            history.update_most_recent_stats(consolidated_stats.clone());
+                        }
+                        history.push(new_stats.clone());
+                    }
+                    // sending only fails if there are no subscribers, which is fine
+                    let _ = update_thread_sender.send(StatsHistoryUpdate::Consolidated {
+                        stats: consolidated_stats,
+                        new_entry: consolidated_new_entry,
+                    });
+                    let _ = update_thread_sender.send(StatsHistoryUpdate::Collected {
+                        stats: new_stats,
+                        new_entry: true,
+                    });
+                    recent_stats = Vec::with_capacity(consolidation_limit.get());
+                } else {
+                    let new_entry = start_new_entry;
+                    {
+                        let mut history = update_thread_stats_history.lock().unwrap();
+                        if start_new_entry {
+                            history.push(new_stats.clone());
+                            start_new_entry = false;
+                        } else {
+                            history.update_most_recent_stats(new_stats.clone());
+                        }
+                    }
+                    let _ = update_thread_sender.send(StatsHistoryUpdate::Collected {
+                        stats: new_stats,
+                        new_entry,
+                    });
+                }
+            }
         });
 
         UpdatingStatsHistory {
             _update_thread: update_thread,
             stats_history: shared_stats_history,
+            update_sender,
+            alerts: shared_alerts,
+            collectors: shared_collectors,
         }
     }
-}
 
-fn consolidate_all_stats(mut stats_list: Vec<AllStats>) -> AllStats {
-    if stats_list.is_empty() {
-        panic!("stats_list must not be empty")
+    /// Subscribes to updates made to the stats history. Only updates made after subscribing will be received.
+    pub fn subscribe(&self) -> broadcast::Receiver<StatsHistoryUpdate> {
+        self.update_sender.subscribe()
     }
+}
 
-    // deal with stats we need to calculate averages for first
-    let mut average_one_min_load_average = 0.0;
-    let mut average_five_min_load_average = 0.0;
-    let mut average_fifteen_min_load_average = 0.0;
-
-    let mut average_per_logical_cpu_loads = Vec::new();
-    let mut average_aggregate_cpu_load = 0.0;
-    let mut average_temp = 0.0;
-
-    let mut average_mem_used = 0.0;
-    let mut max_total_mem = 0;
-
-    let mut average_tcp_used = 0.0;
-    let mut average_tcp_orphaned = 0.0;
-    let mut average_udp_used = 0.0;
-    let mut average_tcp6_used = 0.0;
-    let mut average_udp6_used = 0.0;
-
-    for (i, all_stats) in stats_list.iter().enumerate() {
-        if let Some(load_averages) = &all_stats.general.load_averages {
-            average_one_min_load_average =
-                average_one_min_load_average.updated_average(load_averages.one_minute, i + 1);
-            average_five_min_load_average =
-                average_five_min_load_average.updated_average(load_averages.five_minutes, i + 1);
-            average_fifteen_min_load_average = average_fifteen_min_load_average
-                .updated_average(load_averages.fifteen_minutes, i + 1);
-        }
-
-        if let Some(loads) = &all_stats.cpu.per_logical_cpu_load_percent {
-            average_per_logical_cpu_loads.update_averages(loads, i + 1);
-        }
-
-        if let Some(aggregate) = &all_stats.cpu.aggregate_load_percent {
-            average_aggregate_cpu_load =
-                average_aggregate_cpu_load.updated_average(*aggregate, i + 1);
-        }
-
-        if let Some(temp) = &all_stats.cpu.temp_celsius {
-            average_temp = average_temp.updated_average(*temp, i + 1);
-        }
-
-        if let Some(memory_stats) = &all_stats.memory {
-            average_mem_used = average_mem_used.updated_average(memory_stats.used_mb as f32, i + 1);
-            if memory_stats.total_mb > max_total_mem {
-                max_total_mem = memory_stats.total_mb;
+/// Builds the history to start with. If persistence is enabled, the most recent persisted stats are loaded into it.
+///
+/// # Arguments
+/// * `history_size` - The maximum number of entries to keep in the history.
+/// * `persistence_config` - Configuration for persisting history to disk.
+/// * `filters` - The filters to apply to the loaded stats, in case they changed since the stats were persisted.
+fn load_initial_history(
+    history_size: NonZeroUsize,
+    persistence_config: &HistoryPersistenceConfig,
+    filters: &StatsFilters,
+) -> StatsHistory {
+    match persistence_config {
+        HistoryPersistenceConfig::Enabled { dir, .. } => {
+            match StatsHistory::load_most_recent_from(dir, history_size) {
+                Ok(mut x) => {
+                    x.apply_filters(filters);
+                    x
+                }
+                Err(e) => {
+                    println!("Error loading persisted stats from {:?}: {}", dir, e);
+                    StatsHistory::new(history_size)
+                }
             }
         }
-
-        if let Some(socket_stats) = &all_stats.network.sockets {
-            average_tcp_used =
-                average_tcp_used.updated_average(socket_stats.tcp_in_use as f32, i + 1);
-            average_tcp_orphaned =
-                average_tcp_orphaned.updated_average(socket_stats.tcp_orphaned as f32, i + 1);
-            average_udp_used =
-                average_udp_used.updated_average(socket_stats.udp_in_use as f32, i + 1);
-            average_tcp6_used =
-                average_tcp6_used.updated_average(socket_stats.tcp6_in_use as f32, i + 1);
-            average_udp6_used =
-                average_udp6_used.updated_average(socket_stats.udp6_in_use as f32, i + 1);
-        }
-    }
-
-    let last_stats = stats_list.pop().unwrap(); // this should never panic because we won't get to here if stats_list is empty
-    let general = GeneralStats {
-        uptime_seconds: last_stats.general.uptime_seconds,
-        boot_timestamp: last_stats.general.boot_timestamp,
-        load_averages: Some(LoadAverages {
-            one_minute: average_one_min_load_average,
-            five_minutes: average_five_min_load_average,
-            fifteen_minutes: average_fifteen_min_load_average,
-        }),
-    };
-
-    let filesystems = last_stats.filesystems;
-
-    let network = NetworkStats {
-        interfaces: last_stats.network.interfaces,
-        sockets: Some(SocketStats {
-            tcp_in_use: average_tcp_used.round() as usize,
-            tcp_orphaned: average_tcp_orphaned.round() as usize,
-            udp_in_use: average_udp_used.round() as usize,
-            tcp6_in_use: average_tcp6_used.round() as usize,
-            udp6_in_use: average_udp6_used.round() as usize,
-        }),
-    };
-
-    let collection_time = last_stats.collection_time;
-
-    AllStats {
-        general,
-        cpu: CpuStats {
-            per_logical_cpu_load_percent: Some(average_per_logical_cpu_loads),
-            aggregate_load_percent: Some(average_aggregate_cpu_load),
-            temp_celsius: Some(average_temp),
-        },
-        memory: Some(MemoryStats {
-            used_mb: average_mem_used.round() as u64,
-            total_mb: max_total_mem,
-        }),
-        filesystems,
-        network,
-        collection_time,
+        HistoryPersistenceConfig::Disabled => StatsHistory::new(history_size),
     }
 }
 
 fn persist_stats(stats: &AllStats, dir: &Path, dir_size_limit_bytes: u64) -> io::Result<()> {
-    if !dir.exists() {
-        create_dir_all(dir)?;
-    }
-
     let current_stats_path = dir.join(CURRENT_HISTORY_FILE_NAME);
-    let old_stats_path = dir.join(OLD_HISTORY_FILE_NAME);
 
     // divide size limit by 2 since this swaps between 2 files
     if current_stats_path.exists()
         && current_stats_path.metadata()?.len() >= (dir_size_limit_bytes / 2)
     {
-        rename(&current_stats_path, &old_stats_path)?;
+        rotate_persisted_stats(dir)?;
+    }
+
+    append_persisted_stats(stats, dir)
+}
+
+/// Adds stats to the end of the current history file in the provided directory, creating the directory if it doesn't exist.
+pub fn append_persisted_stats(stats: &AllStats, dir: &Path) -> io::Result<()> {
+    if !dir.exists() {
+        create_dir_all(dir)?;
     }
 
     let mut current_stats_file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(current_stats_path)?;
+        .open(dir.join(CURRENT_HISTORY_FILE_NAME))?;
     writeln!(current_stats_file, "{}", serde_json::to_string(stats)?)?;
 
     Ok(())
 }
 
-trait MovingAverage<T> {
-    /// Updates the average to take into account a new value.
-    ///
-    /// # Arguments
-    /// * `new_value` - The new value to add to the average.
-    /// * `n` - The number of values in the dataset (including the new one).
-    ///
-    /// Returns the updated average.
-    fn updated_average(self, new_value: T, n: usize) -> T;
+/// Replaces the old history file in the provided directory with the current one, so new stats are added to an empty file.
+pub fn rotate_persisted_stats(dir: &Path) -> io::Result<()> {
+    rename(
+        dir.join(CURRENT_HISTORY_FILE_NAME),
+        dir.join(OLD_HISTORY_FILE_NAME),
+    )
 }
 
-impl MovingAverage<f32> for f32 {
-    fn updated_average(self, new_value: f32, n: usize) -> f32 {
-        self + ((new_value - self) / n as f32)
+/// Gets the collection times of the oldest and newest persisted stats in the provided directory, or `None` if there aren't any. Only the first and last lines of the history files are read, and only their collection times are parsed, so this is much faster than loading the stats.
+pub fn persisted_stats_time_range(
+    dir: &Path,
+) -> io::Result<Option<(DateTime<Local>, DateTime<Local>)>> {
+    let old_path = dir.join(OLD_HISTORY_FILE_NAME);
+    let current_path = dir.join(CURRENT_HISTORY_FILE_NAME);
+    let first = match first_line(&old_path)? {
+        Some(x) => x,
+        None => match first_line(&current_path)? {
+            Some(x) => x,
+            None => return Ok(None),
+        },
+    };
+    let last = match last_line(&current_path)? {
+        Some(x) => x,
+        // there's a first line, so there's a last line in one of the files
+        None => last_line(&old_path)?.unwrap_or_else(|| first.clone()),
+    };
+
+    Ok(Some((
+        parse_collection_time(&first)?,
+        parse_collection_time(&last)?,
+    )))
+}
+
+/// Gets the collection time of the oldest stats in the current history file in the provided directory, or `None` if it's empty.
+pub fn current_persisted_stats_start_time(dir: &Path) -> io::Result<Option<DateTime<Local>>> {
+    match first_line(&dir.join(CURRENT_HISTORY_FILE_NAME))? {
+        Some(x) => Ok(Some(parse_collection_time(&x)?)),
+        None => Ok(None),
     }
 }
 
-trait MovingAverageCollection<T> {
-    /// Updates the averages to take into account a new set of values.
-    ///
-    /// # Arguments
-    /// * `new_values` - The new values to add to the averages. If larger than `self`, `self` will be padded with zeroes to match its size.
-    /// * `n` - The number of sets of values in the dataset (including the new ones).
-    fn update_averages(&mut self, new_values: &[T], n: usize);
+/// Just the collection time of persisted stats, so it can be read without parsing the rest of the stats.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedCollectionTime {
+    collection_time: DateTime<Local>,
+}
+
+/// Parses the collection time from a line in a history file.
+fn parse_collection_time(line: &str) -> io::Result<DateTime<Local>> {
+    let parsed: PersistedCollectionTime = serde_json::from_str(line.trim())?;
+    Ok(parsed.collection_time)
 }
 
-impl MovingAverageCollection<f32> for Vec<f32> {
-    fn update_averages(&mut self, new_values: &[f32], n: usize) {
-        while self.len() < new_values.len() {
-            self.push(0.0);
+/// Gets the first non-empty line in the file at the provided path, or `None` if it doesn't exist or has no non-empty lines.
+fn first_line(path: &Path) -> io::Result<Option<String>> {
+    if path.exists() {
+        let file = File::open(path)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line));
+            }
         }
+    }
+
+    Ok(None)
+}
+
+/// Gets the last non-empty line in the file at the provided path, or `None` if it doesn't exist or has no non-empty lines. The file is read backwards from the end, so the rest of it isn't read.
+fn last_line(path: &Path) -> io::Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
 
-        for (i, new_value) in new_values.iter().enumerate() {
-            self[i] = self[i] + ((new_value - self[i]) / n as f32)
+    let mut file = File::open(path)?;
+    // the position in the file that `end_bytes` starts at
+    let mut position = file.metadata()?.len();
+    let mut end_bytes = Vec::new();
+    loop {
+        let content_end = end_bytes
+            .iter()
+            .rposition(|x: &u8| !x.is_ascii_whitespace())
+            .map(|i| i + 1);
+        if let Some(content_end) = content_end {
+            let line_start = end_bytes[..content_end].iter().rposition(|x| *x == b'\n');
+            if line_start.is_some() || position == 0 {
+                let line = end_bytes[line_start.map(|i| i + 1).unwrap_or(0)..content_end].to_vec();
+                return String::from_utf8(line)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        } else if position == 0 {
+            return Ok(None);
         }
+
+        let chunk_size = position.min(LAST_LINE_CHUNK_SIZE);
+        position -= chunk_size;
+        let mut chunk = vec![0; chunk_size as usize];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&end_bytes);
+        end_bytes = chunk;
     }
 }
 
@@ -299,24 +401,38 @@ impl StatsHistory {
     /// # Arguments
     /// * `dir` - The directory to find persisted stats history files in.
     pub fn load_from(dir: &Path) -> io::Result<StatsHistory> {
-        let mut stats = Vec::new();
-
-        let old_stats_path = dir.join(OLD_HISTORY_FILE_NAME);
-        let current_stats_path = dir.join(CURRENT_HISTORY_FILE_NAME);
-
-        add_stats_from_file(old_stats_path, &mut stats)?;
-        add_stats_from_file(current_stats_path, &mut stats)?;
+        Ok(StatsHistory::from_stats(load_persisted_stats(dir)?))
+    }
 
+    /// Creates a `StatsHistory` that holds exactly the provided stats.
+    ///
+    /// # Arguments
+    /// * `stats` - The stats to hold, from oldest to newest.
+    pub fn from_stats(stats: Vec<AllStats>) -> StatsHistory {
         match NonZeroUsize::new(stats.len()) {
-            Some(size) => Ok(StatsHistory {
+            Some(size) => StatsHistory {
                 max_size: size,
                 stats,
                 most_recent_index: size.get() - 1,
-            }),
-            None => Ok(StatsHistory::new(NonZeroUsize::new(1).unwrap())),
+            },
+            None => StatsHistory::new(NonZeroUsize::new(1).unwrap()),
         }
     }
 
+    /// Loads the most recent stats history from the provided directory.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory to find persisted stats history files in.
+    /// * `max_size` - The maximum number of entries to hold in this history. Only the newest entries are kept.
+    pub fn load_most_recent_from(dir: &Path, max_size: NonZeroUsize) -> io::Result<StatsHistory> {
+        let mut history = StatsHistory::new(max_size);
+        for stats in load_persisted_stats(dir)? {
+            history.push(stats);
+        }
+
+        Ok(history)
+    }
+
     /// Adds stats to the history.
     ///
     /// # Arguments
@@ -345,6 +461,21 @@ impl StatsHistory {
         }
     }
 
+    /// Removes the mounts and network interfaces that don't match the provided filters from every entry in the history.
+    ///
+    /// # Arguments
+    /// * `filters` - The filters to choose which mounts and network interfaces to keep.
+    pub fn apply_filters(&mut self, filters: &StatsFilters) {
+        for stats in &mut self.stats {
+            filters.apply(stats);
+        }
+    }
+
+    /// Gets the maximum number of entries this history can hold.
+    pub fn max_size(&self) -> usize {
+        self.max_size.get()
+    }
+
     /// Gets the most recently added stats from the history. Returns `None` if the history is empty.
     pub fn get_most_recent_stats(&self) -> Option<&AllStats> {
         if self.stats.is_empty() {
@@ -354,11 +485,78 @@ impl StatsHistory {
         }
     }
 
+    /// Gets the stats in the history that match the provided query, from oldest to newest.
+    ///
+    /// # Arguments
+    /// * `query` - The query to match stats against.
+    pub fn query(&self, query: &HistoryQuery) -> Vec<AllStats> {
+        let mut matching_stats: Vec<AllStats> = self
+            .into_iter()
+            .filter(|stats| match query.from {
+                Some(from) => stats.collection_time >= from,
+                None => true,
+            })
+            .filter(|stats| match query.to {
+                Some(to) => stats.collection_time <= to,
+                None => true,
+            })
+            .cloned()
+            .collect();
+
+        if let Some(limit) = query.limit {
+            if matching_stats.len() > limit {
+                matching_stats.drain(..(matching_stats.len() - limit));
+            }
+        }
+
+        matching_stats
+    }
+
     fn get_next_index(&self) -> usize {
         index_after(self.most_recent_index, self.max_size)
     }
 }
 
+/// Loads all the persisted stats from the provided directory, from oldest to newest.
+pub fn load_persisted_stats(dir: &Path) -> io::Result<Vec<AllStats>> {
+    let mut stats = Vec::new();
+
+    let old_stats_path = dir.join(OLD_HISTORY_FILE_NAME);
+    let current_stats_path = dir.join(CURRENT_HISTORY_FILE_NAME);
+
+    add_stats_from_file(old_stats_path, &mut stats)?;
+    add_stats_from_file(current_stats_path, &mut stats)?;
+
+    Ok(stats)
+}
+
+/// Loads the persisted stats from the provided directory that were collected after the provided time, from oldest to newest. Older stats are skipped without being fully parsed.
+pub fn load_persisted_stats_after(dir: &Path, after: DateTime<Local>) -> io::Result<Vec<AllStats>> {
+    let mut stats = Vec::new();
+    for path in [
+        dir.join(OLD_HISTORY_FILE_NAME),
+        dir.join(CURRENT_HISTORY_FILE_NAME),
+    ] {
+        if !path.exists() {
+            continue;
+        }
+
+        let file = File::open(path)?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if parse_collection_time(trimmed)? > after {
+                stats.push(serde_json::from_str(trimmed)?);
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
 /// Adds stats from the file at the provided path (if it exists) to the provided list of stats
 fn add_stats_from_file(path: PathBuf, stats: &mut Vec<AllStats>) -> io::Result<()> {
     if path.exists() {
@@ -425,3 +623,84 @@ impl<'a> Iterator for StatsHistoryIterator<'a> {
         Some(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Builds a line for a history file, padded so it's longer than the chunks history files are read backwards in.
+    fn history_line(collection_time: &str) -> String {
+        format!(
+            "{{\"padding\":\"{}\",\"collectionTime\":\"{}\"}}\n",
+            "x".repeat(LAST_LINE_CHUNK_SIZE as usize + 100),
+            collection_time
+        )
+    }
+
+    fn time(x: &str) -> DateTime<Local> {
+        DateTime::parse_from_rfc3339(x)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    #[test]
+    fn time_range_of_empty_dir_is_none() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(persisted_stats_time_range(dir.path()).unwrap().is_none());
+
+        fs::write(dir.path().join(CURRENT_HISTORY_FILE_NAME), "\n\n").unwrap();
+        assert!(persisted_stats_time_range(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn time_range_spans_old_and_current_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(OLD_HISTORY_FILE_NAME),
+            history_line("2021-03-15T10:00:00Z") + &history_line("2021-03-15T11:00:00Z"),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(CURRENT_HISTORY_FILE_NAME),
+            history_line("2021-03-15T12:00:00Z") + &history_line("2021-03-15T13:00:00Z") + "\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some((time("2021-03-15T10:00:00Z"), time("2021-03-15T13:00:00Z"))),
+            persisted_stats_time_range(dir.path()).unwrap()
+        );
+        assert_eq!(
+            Some(time("2021-03-15T12:00:00Z")),
+            current_persisted_stats_start_time(dir.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn time_range_of_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(OLD_HISTORY_FILE_NAME),
+            history_line("2021-03-15T10:00:00Z") + &history_line("2021-03-15T11:00:00Z"),
+        )
+        .unwrap();
+        assert_eq!(
+            Some((time("2021-03-15T10:00:00Z"), time("2021-03-15T11:00:00Z"))),
+            persisted_stats_time_range(dir.path()).unwrap()
+        );
+
+        // a single line without a trailing newline is both the first and last line
+        fs::remove_file(dir.path().join(OLD_HISTORY_FILE_NAME)).unwrap();
+        fs::write(
+            dir.path().join(CURRENT_HISTORY_FILE_NAME),
+            history_line("2021-03-15T12:00:00Z").trim_end(),
+        )
+        .unwrap();
+        assert_eq!(
+            Some((time("2021-03-15T12:00:00Z"), time("2021-03-15T12:00:00Z"))),
+            persisted_stats_time_range(dir.path()).unwrap()
+        );
+    }
+}
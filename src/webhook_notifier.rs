@@ -0,0 +1,264 @@
+//! Webhook notifications for alerts.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::mpsc::{channel, Sender},
+    thread,
+    time::Duration,
+};
+
+use rocket_dyn_templates::tera::{Context, Tera};
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::{AlertNotification, AlertNotifier, AlertStatus};
+use crate::stats::AllStats;
+
+const BODY_TEMPLATE_NAME: &str = "webhook_body";
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_INITIAL_BACKOFF_SECONDS: u64 = 1;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+/// Configuration for sending alert notifications to a webhook.
+#[derive(Clone, Deserialize)]
+pub struct WebhookConfig {
+    /// The URL to send notifications to.
+    pub url: String,
+    /// Extra headers to include in each request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// The path to the template for request bodies, relative to the template directory.
+    pub body_template: String,
+    /// The number of times to retry sending a notification after the first attempt fails.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// The number of seconds to wait before the first retry. The wait doubles after each retry.
+    #[serde(default = "default_initial_backoff_seconds")]
+    pub initial_backoff_seconds: u64,
+    /// The number of seconds to wait for each request to complete before giving up on it.
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_initial_backoff_seconds() -> u64 {
+    DEFAULT_INITIAL_BACKOFF_SECONDS
+}
+
+fn default_timeout_seconds() -> u64 {
+    DEFAULT_TIMEOUT_SECONDS
+}
+
+impl fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // header values often hold credentials, so only the names are included so they don't end up in logs
+        let header_names: Vec<&String> = self.headers.keys().collect();
+        f.debug_struct("WebhookConfig")
+            .field("url", &self.url)
+            .field("header_names", &header_names)
+            .field("body_template", &self.body_template)
+            .field("max_retries", &self.max_retries)
+            .field("initial_backoff_seconds", &self.initial_backoff_seconds)
+            .field("timeout_seconds", &self.timeout_seconds)
+            .finish()
+    }
+}
+
+/// Context for the webhook body template.
+#[derive(Serialize)]
+struct WebhookContext<'a> {
+    /// The status of the alert the notification is for.
+    status: &'a AlertStatus,
+    /// The stats that caused the alert to start firing or be resolved.
+    stats: &'a AllStats,
+}
+
+/// Sends alert notifications to a webhook. Requests are sent from a separate thread.
+pub struct WebhookNotifier {
+    /// Sends notifications to the thread that sends requests.
+    sender: Sender<AlertNotification>,
+}
+
+impl WebhookNotifier {
+    /// Creates a `WebhookNotifier` and starts the thread that sends requests.
+    ///
+    /// # Arguments
+    /// * `config` - Configuration for sending requests.
+    /// * `template_dir` - The directory templates are loaded from.
+    pub fn new(config: WebhookConfig, template_dir: &Path) -> Result<WebhookNotifier, String> {
+        let template_path = template_dir.join(&config.body_template);
+        let mut tera = Tera::default();
+        tera.add_template_file(&template_path, Some(BODY_TEMPLATE_NAME))
+            .map_err(|e| format!("error loading template {:?}: {}", template_path, e))?;
+
+        let (sender, receiver) = channel::<AlertNotification>();
+        thread::spawn(move || {
+            for notification in receiver {
+                let context = WebhookContext {
+                    status: &notification.status,
+                    stats: &notification.stats,
+                };
+                let body = match Context::from_serialize(&context)
+                    .and_then(|context| tera.render(BODY_TEMPLATE_NAME, &context))
+                {
+                    Ok(x) => x,
+                    Err(e) => {
+                        println!("Error rendering webhook body: {}", e);
+                        continue;
+                    }
+                };
+
+                send_with_retries(&config, &body);
+            }
+        });
+
+        Ok(WebhookNotifier { sender })
+    }
+}
+
+impl AlertNotifier for WebhookNotifier {
+    fn notify(&self, notification: AlertNotification) {
+        if self.sender.send(notification).is_err() {
+            println!("Error sending webhook notification: webhook thread has stopped");
+        }
+    }
+}
+
+/// Sends a request with the provided body to the configured webhook, retrying with exponential backoff if it fails.
+fn send_with_retries(config: &WebhookConfig, body: &str) {
+    let mut backoff = Duration::from_secs(config.initial_backoff_seconds);
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+
+        let mut request = ureq::post(&config.url)
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .set("Content-Type", "application/json");
+        for (name, value) in &config.headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_string(body) {
+            Ok(_) => return,
+            Err(e) => println!(
+                "Error sending webhook notification to {} (attempt {} of {}): {}",
+                config.url,
+                attempt + 1,
+                config.max_retries + 1,
+                e
+            ),
+        }
+    }
+
+    println!(
+        "Giving up on sending webhook notification to {}",
+        config.url
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use chrono::Local;
+
+    use super::*;
+    use crate::alerts::{AlertRule, Alerts, Comparator};
+    use crate::stats::CpuStats;
+
+    /// Accepts HTTP requests on the provided listener forever, sending each received request to `requests` and responding with an empty 200 response.
+    fn run_http_sink(listener: TcpListener, requests: mpsc::Sender<String>) {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+
+            requests.send(request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        }
+    }
+
+    fn config(port: u16) -> WebhookConfig {
+        WebhookConfig {
+            url: format!("http://127.0.0.1:{}/receive", port),
+            headers: vec![("Authorization".to_string(), "Bearer abc123".to_string())]
+                .into_iter()
+                .collect(),
+            body_template: "webhook.json.tera".to_string(),
+            max_retries: 0,
+            initial_backoff_seconds: 1,
+            timeout_seconds: 5,
+        }
+    }
+
+    #[test]
+    fn firing_alert_is_posted_to_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (request_sender, request_receiver) = mpsc::channel();
+        thread::spawn(move || run_http_sink(listener, request_sender));
+
+        let notifier = WebhookNotifier::new(config(port), Path::new("templates")).unwrap();
+        let rule = AlertRule {
+            name: "High CPU usage".to_string(),
+            metric: "cpu.aggregateLoadPercent".to_string(),
+            comparator: Comparator::GreaterThan,
+            threshold: 90.0,
+            for_seconds: 0,
+        };
+        let mut alerts = Alerts::new(vec![rule], vec![Box::new(notifier)]);
+        let mut stats = AllStats::empty(Local::now());
+        stats.cpu = Some(CpuStats {
+            aggregate_load_percent: Some(95.0),
+            ..CpuStats::default()
+        });
+        alerts.evaluate(&stats);
+
+        let request = request_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(request.starts_with("POST /receive "));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer abc123\r\n"));
+        assert!(request.contains("\"alert\": \"High CPU usage\""));
+        assert!(request.contains("\"state\": \"firing\""));
+        assert!(request.contains("\"value\": 95.0"));
+    }
+
+    #[test]
+    fn debug_leaves_out_header_values() {
+        let debug = format!("{:?}", config(80));
+        assert!(debug.contains("Authorization"));
+        assert!(!debug.contains("abc123"));
+    }
+}